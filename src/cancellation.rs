@@ -0,0 +1,80 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use gluon::base::fnv::FnvMap;
+
+/// The error code the language server protocol uses for requests which were cancelled
+pub const REQUEST_CANCELLED: i64 = -32800;
+
+/// Flag which is set when the work it belongs to is no longer needed
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+struct Requests {
+    pending: FnvMap<String, CancellationToken>,
+    current: CancellationToken,
+}
+
+/// Keeps track of the cancellation tokens of every request which has been received but not yet
+/// responded to. Requests are identified by their json-rpc id serialized as a string.
+#[derive(Clone)]
+pub struct Cancellation(Arc<Mutex<Requests>>);
+
+impl Cancellation {
+    pub fn new() -> Cancellation {
+        Cancellation(Arc::new(Mutex::new(Requests {
+            pending: FnvMap::default(),
+            current: CancellationToken::new(),
+        })))
+    }
+
+    /// Registers a newly received request
+    pub fn register(&self, id: &str) {
+        self.0.lock().unwrap().pending.insert(id.into(), CancellationToken::new());
+    }
+
+    /// Cancels the request `id` if it has not already been responded to
+    pub fn cancel(&self, id: &str) {
+        if let Some(token) = self.0.lock().unwrap().pending.get(id) {
+            token.cancel();
+        }
+    }
+
+    /// Marks `id` as the request which is currently being handled and returns its token. Messages
+    /// without an id (notifications) get a token which is never cancelled.
+    pub fn start(&self, id: Option<&str>) -> CancellationToken {
+        let mut requests = self.0.lock().unwrap();
+        let token = id.and_then(|id| requests.pending.get(id).cloned())
+            .unwrap_or_else(CancellationToken::new);
+        requests.current = token.clone();
+        token
+    }
+
+    /// Stops tracking `id` once it has been responded to
+    pub fn finish(&self, id: Option<&str>) {
+        let mut requests = self.0.lock().unwrap();
+        if let Some(id) = id {
+            requests.pending.remove(id);
+        }
+        requests.current = CancellationToken::new();
+    }
+
+    /// Returns the token of the request which is currently being handled
+    pub fn current(&self) -> CancellationToken {
+        self.0.lock().unwrap().current.clone()
+    }
+}
@@ -0,0 +1,244 @@
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{self, Value};
+
+use url::Url;
+
+use languageserver_types::{DiagnosticSeverity, NumberOrString};
+
+use cancellation::CancellationToken;
+use config::{self, Config};
+use diagnostic::Report;
+use position::PositionEncoding;
+use settings::Settings;
+
+/// How `check_files` reports diagnostics
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CheckFormat {
+    /// Messages with the file, line, column and source snippet of each diagnostic
+    Human,
+    /// A json array with an object containing the `path` and (language server) `diagnostics` of
+    /// each file
+    Json,
+}
+
+struct FileDiagnostics {
+    path: PathBuf,
+    source: String,
+    diagnostics: Vec<Report>,
+}
+
+impl FileDiagnostics {
+    /// The result of a file which could not be checked at all
+    fn unreadable(path: PathBuf, message: String) -> FileDiagnostics {
+        FileDiagnostics {
+            path: path,
+            source: String::new(),
+            diagnostics: vec![Report::error("io-error", message)],
+        }
+    }
+}
+
+/// Typechecks every gluon file in `paths` (directories are searched recursively) and writes the
+/// diagnostics to stdout. Returns the number of errors found.
+pub fn check_files(config: &Config, paths: &[PathBuf], format: CheckFormat) -> io::Result<usize> {
+    try!(config::init_logger(config));
+    let thread = ::new_check_vm(config.import_paths.clone());
+
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            ::find_gluon_files(path, &mut files);
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    let settings = Settings::default();
+    // Human readable columns count characters so that the marker lines up with the source
+    // snippet while json uses the default encoding of the language server protocol
+    let encoding = match format {
+        CheckFormat::Human => PositionEncoding::Utf32,
+        CheckFormat::Json => PositionEncoding::default(),
+    };
+    let mut results = Vec::new();
+    // Diagnostics of modules which failed to be imported, keyed by the uri of their file
+    let mut imported = Vec::new();
+    for path in files {
+        // Files which can not be read are reported like any other error so that the remaining
+        // files are still checked
+        let mut source = String::new();
+        let read = fs::File::open(&path).and_then(|mut file| file.read_to_string(&mut source));
+        if let Err(err) = read {
+            let message = format!("Unable to read `{}`: {}", path.display(), err);
+            results.push(FileDiagnostics::unreadable(path, message));
+            continue;
+        }
+        let uri = fs::canonicalize(&path).ok().and_then(|path| Url::from_file_path(path).ok());
+        let uri = match uri {
+            Some(uri) => uri,
+            None => {
+                let message = format!("Invalid path `{}`", path.display());
+                results.push(FileDiagnostics::unreadable(path, message));
+                continue;
+            }
+        };
+        let token = CancellationToken::new();
+        let diagnostics = match ::typecheck(&thread, &token, uri.as_str(), &source) {
+            Some(Err(err)) => {
+                let mut publish = |uri, diagnostics| imported.push((uri, diagnostics));
+                ::module_error_diagnostics(&thread,
+                                           &settings,
+                                           encoding,
+                                           uri.as_str(),
+                                           &source,
+                                           err,
+                                           &mut publish)
+            }
+            Some(Ok(())) => {
+                ::lint_diagnostics(&thread, uri.as_str(), &source, &settings.lints, encoding)
+            }
+            None => vec![],
+        };
+        results.push(FileDiagnostics {
+            path: path,
+            source: source,
+            diagnostics: diagnostics,
+        });
+    }
+
+    let checked = results.len();
+    // Imported files which were checked on their own already report the same errors
+    for (uri, diagnostics) in imported {
+        let path = match Url::parse(&uri).ok().and_then(|url| url.to_file_path().ok()) {
+            Some(path) => path,
+            None => continue,
+        };
+        let reported = results.iter().any(|file| {
+            file.path == path || fs::canonicalize(&file.path).ok().as_ref() == Some(&path)
+        });
+        if !reported {
+            // The diagnostics are printed without a snippet if the file can not be read
+            let mut source = String::new();
+            let _ = fs::File::open(&path).and_then(|mut file| file.read_to_string(&mut source));
+            results.push(FileDiagnostics {
+                path: path,
+                source: source,
+                diagnostics: diagnostics,
+            });
+        }
+    }
+
+    let (errors, warnings) = {
+        let count = |severity| {
+            results.iter()
+                .flat_map(|file| &file.diagnostics)
+                .filter(|report| report.diagnostic.severity == Some(severity))
+                .count()
+        };
+        (count(DiagnosticSeverity::Error), count(DiagnosticSeverity::Warning))
+    };
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    match format {
+        CheckFormat::Human => {
+            for file in &results {
+                for report in &file.diagnostics {
+                    try!(write_diagnostic(&mut stdout, &file.path, &file.source, report));
+                }
+            }
+            try!(writeln!(stdout,
+                          "Checked {} file{}, found {} error{} and {} warning{}",
+                          checked,
+                          if checked == 1 { "" } else { "s" },
+                          errors,
+                          if errors == 1 { "" } else { "s" },
+                          warnings,
+                          if warnings == 1 { "" } else { "s" }));
+        }
+        CheckFormat::Json => {
+            let files = results.into_iter()
+                .map(|file| {
+                    let mut object = BTreeMap::new();
+                    object.insert("path".to_string(),
+                                  Value::String(file.path.display().to_string()));
+                    object.insert("diagnostics".to_string(),
+                                  Value::Array(file.diagnostics
+                                      .iter()
+                                      .map(Report::to_value)
+                                      .collect()));
+                    Value::Object(object)
+                })
+                .collect();
+            let json = try!(serde_json::to_string_pretty(&Value::Array(files))
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)));
+            try!(writeln!(stdout, "{}", json));
+        }
+    }
+    Ok(errors)
+}
+
+fn severity_name(severity: &Option<DiagnosticSeverity>) -> &'static str {
+    match *severity {
+        Some(DiagnosticSeverity::Warning) => "warning",
+        Some(DiagnosticSeverity::Information) => "info",
+        Some(DiagnosticSeverity::Hint) => "hint",
+        Some(DiagnosticSeverity::Error) | None => "error",
+    }
+}
+
+fn write_diagnostic<W>(writer: &mut W, path: &Path, source: &str, report: &Report) -> io::Result<()>
+    where W: Write,
+{
+    let diagnostic = &report.diagnostic;
+    let start = &diagnostic.range.start;
+    let end = &diagnostic.range.end;
+    try!(writeln!(writer,
+                  "{}: {}",
+                  severity_name(&diagnostic.severity),
+                  diagnostic.message));
+    try!(writeln!(writer,
+                  "  --> {}:{}:{}",
+                  path.display(),
+                  start.line + 1,
+                  start.character + 1));
+    if let Some(line) = source.lines().nth(start.line as usize) {
+        let number = (start.line + 1).to_string();
+        let gutter = " ".repeat(number.len());
+        // Keep tabs so that the marker lines up with the source line
+        let indent: String = line.chars()
+            .take(start.character as usize)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let line_length = line.chars().count() as u64;
+        let marker_end = if end.line == start.line { end.character } else { line_length };
+        let marker = "^".repeat(cmp::max(marker_end.saturating_sub(start.character), 1) as usize);
+        try!(writeln!(writer, "{} |", gutter));
+        try!(writeln!(writer, "{} | {}", number, line));
+        try!(writeln!(writer, "{} | {}{}", gutter, indent, marker));
+    }
+    match diagnostic.code {
+        Some(NumberOrString::String(ref code)) => try!(writeln!(writer, "  = code: {}", code)),
+        Some(NumberOrString::Number(code)) => try!(writeln!(writer, "  = code: {}", code)),
+        None => (),
+    }
+    for related in &report.related {
+        let location = &related.location;
+        let file = Url::parse(&location.uri)
+            .ok()
+            .and_then(|url| url.to_file_path().ok())
+            .map_or_else(|| location.uri.clone(), |path| path.display().to_string());
+        try!(writeln!(writer,
+                      "  = note: {} at {}:{}:{}",
+                      related.message,
+                      file,
+                      location.range.start.line + 1,
+                      location.range.start.character + 1));
+    }
+    writeln!(writer, "")
+}
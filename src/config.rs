@@ -0,0 +1,104 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use log::{self, Log, LogLevelFilter, LogMetadata, LogRecord};
+
+use env_logger::LogBuilder;
+
+/// How the server communicates with the client
+#[derive(Clone, Debug, PartialEq)]
+pub enum Transport {
+    /// Read messages from stdin and write messages to stdout
+    Stdio,
+    /// Accept clients on a TCP address, serving one at a time
+    Listen(String),
+    /// Connect to a client listening on a TCP address
+    Connect(String),
+}
+
+/// Configuration for launching the language server with `run`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub transport: Transport,
+    /// Paths searched by `import` in addition to those from the client's settings
+    pub import_paths: Vec<PathBuf>,
+    /// Where to write log messages. Defaults to stderr.
+    pub log_file: Option<PathBuf>,
+    /// The maximum level of messages which are logged. If this is not set the level is read from
+    /// the `RUST_LOG` environment variable.
+    pub log_level: Option<LogLevelFilter>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            transport: Transport::Stdio,
+            import_paths: Vec::new(),
+            log_file: None,
+            log_level: None,
+        }
+    }
+}
+
+struct FileLogger {
+    level: LogLevelFilter,
+    file: Mutex<File>,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &LogMetadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &LogRecord) {
+        if self.enabled(record.metadata()) {
+            let mut file = self.file.lock().unwrap();
+            let _ = writeln!(file,
+                             "{}:{}: {}",
+                             record.level(),
+                             record.location().module_path(),
+                             record.args());
+        }
+    }
+}
+
+fn open_log_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Installs the logger described by `config`. Does nothing if a logger has already been installed
+/// (by an embedder or an earlier call).
+pub fn init_logger(config: &Config) -> io::Result<()> {
+    match config.log_file {
+        Some(ref path) => {
+            let file = try!(open_log_file(path));
+            let level = config.log_level
+                .or_else(|| ::std::env::var("RUST_LOG").ok().and_then(|level| level.parse().ok()))
+                .unwrap_or(LogLevelFilter::Error);
+            let _ = log::set_logger(|max_level| {
+                max_level.set(level);
+                Box::new(FileLogger {
+                    level: level,
+                    file: Mutex::new(file),
+                })
+            });
+        }
+        None => {
+            let mut builder = LogBuilder::new();
+            match config.log_level {
+                Some(level) => {
+                    builder.filter(None, level);
+                }
+                None => {
+                    if let Ok(filters) = ::std::env::var("RUST_LOG") {
+                        builder.parse(&filters);
+                    }
+                }
+            }
+            let _ = builder.init();
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+
+use serde_json::value::{Value, to_value};
+
+use gluon::base::ast::{Expr, Pattern, SpannedExpr};
+use gluon::base::pos::{BytePos, Span};
+use gluon::base::symbol::Symbol;
+use gluon::check::typecheck::TypeError;
+
+use languageserver_types::{Diagnostic, DiagnosticSeverity, Location, NumberOrString};
+
+use query;
+
+/// The `source` of every diagnostic reported by the server, lets users filter them in the client
+pub const SOURCE: &'static str = "gluon";
+
+/// A location related to a diagnostic, such as the binding which caused a type error
+#[derive(Clone, Debug, PartialEq)]
+pub struct RelatedInformation {
+    pub location: Location,
+    pub message: String,
+}
+
+/// A diagnostic along with its related information. `languageserver_types` does not yet know
+/// about `relatedInformation` so this is serialized by hand.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub diagnostic: Diagnostic,
+    pub related: Vec<RelatedInformation>,
+}
+
+impl Report {
+    /// Creates an error with `code` and `message`. The range is left at the start of the file.
+    pub fn error(code: &str, message: String) -> Report {
+        Report::new(DiagnosticSeverity::Error, code, message)
+    }
+
+    /// Creates a warning with `code` and `message`
+    pub fn warning(code: &str, message: String) -> Report {
+        Report::new(DiagnosticSeverity::Warning, code, message)
+    }
+
+    fn new(severity: DiagnosticSeverity, code: &str, message: String) -> Report {
+        Report {
+            diagnostic: Diagnostic {
+                message: message,
+                severity: Some(severity),
+                code: Some(NumberOrString::String(code.into())),
+                source: Some(SOURCE.into()),
+                ..Diagnostic::default()
+            },
+            related: Vec::new(),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        let mut value = to_value(&self.diagnostic);
+        if !self.related.is_empty() {
+            if let Some(map) = value.as_object_mut() {
+                let related = self.related
+                    .iter()
+                    .map(|related| {
+                        let mut map = BTreeMap::new();
+                        map.insert("location".to_string(), to_value(&related.location));
+                        map.insert("message".to_string(), Value::String(related.message.clone()));
+                        Value::Object(map)
+                    })
+                    .collect();
+                map.insert("relatedInformation".to_string(), Value::Array(related));
+            }
+        }
+        value
+    }
+}
+
+/// Returns the code of a type error. Codes stay the same between versions so that clients can
+/// filter on them.
+pub fn type_error_code<I>(err: &TypeError<I>) -> &'static str {
+    match *err {
+        TypeError::UndefinedVariable(..) => "undefined-variable",
+        TypeError::NotAFunction(..) => "not-a-function",
+        TypeError::UndefinedType(..) => "undefined-type",
+        TypeError::UndefinedField(..) => "undefined-field",
+        TypeError::PatternError(..) => "pattern-error",
+        TypeError::Unification(..) => "type-mismatch",
+        TypeError::KindError(..) => "kind-error",
+        TypeError::DuplicateTypeDefinition(..) => "duplicate-type-definition",
+        TypeError::DuplicateField(..) => "duplicate-field",
+        TypeError::InvalidProjection(..) => "invalid-projection",
+        TypeError::Rename(..) => "rename-error",
+        TypeError::UndefinedRecord { .. } => "undefined-record",
+        TypeError::EmptyCase => "empty-case",
+    }
+}
+
+/// Finds the binding which fixed the expected type of the expression at `span`. That is the
+/// function which `span` is passed to or which is applied within `span`, or else the `let` binding
+/// whose type annotation `span` has to match, either as the whole expression of the binding or as
+/// a field of the record it is bound to. Returns the name and the span of the binding.
+pub fn expected_type_binding<'a>(source: &str,
+                                 expr: &'a SpannedExpr<Symbol>,
+                                 span: Span<BytePos>)
+                                 -> Option<(&'a str, Span<BytePos>)> {
+    function_binding(source, expr, span).or_else(|| annotated_binding(source, expr, span))
+}
+
+fn function_binding<'a>(source: &str,
+                        expr: &'a SpannedExpr<Symbol>,
+                        span: Span<BytePos>)
+                        -> Option<(&'a str, Span<BytePos>)> {
+    let func = match expr_with_span(expr, span).map(|expr| &expr.value) {
+        Some(&Expr::App(ref func, _)) => Some(&**func),
+        _ => query::call_at(source, expr, span.start).map(|(func, _)| func),
+    };
+    let symbol = match func.map(|func| &func.value) {
+        Some(&Expr::Ident(ref id)) => &id.name,
+        _ => return None,
+    };
+    let occurrences = query::occurrences(source, expr);
+    query::binding(&occurrences, symbol).map(|binding| (query::name(symbol), binding.span))
+}
+
+/// Finds the innermost `let` binding with a type annotation which is bound to `span`, see
+/// `is_field_value`. Mismatches with the annotation itself are reported at the name of the binding.
+fn annotated_binding<'a>(source: &str,
+                         expr: &'a SpannedExpr<Symbol>,
+                         span: Span<BytePos>)
+                         -> Option<(&'a str, Span<BytePos>)> {
+    // Every sub expression is searched as bindings are not spanned by their bodies
+    let inner = query::sub_exprs(expr)
+        .into_iter()
+        .filter_map(|sub_expr| annotated_binding(source, sub_expr, span))
+        .next();
+    if inner.is_some() {
+        return inner;
+    }
+    let bindings = match expr.value {
+        Expr::LetBindings(ref bindings, _) => bindings,
+        _ => return None,
+    };
+    bindings.iter()
+        .filter(|bind| bind.name.span == span || is_field_value(&bind.expr, span))
+        .filter(|bind| {
+            // The type of a binding is always filled in by the typechecker so look for the
+            // annotation in the source between the name (and arguments) and the expression
+            let (start, end) = (bind.name.span.end.to_usize(), bind.expr.span.start.to_usize());
+            start <= end && end <= source.len() && source[start..end].contains(':')
+        })
+        .filter_map(|bind| match bind.name.value {
+            Pattern::Ident(ref id) => Some((query::name(&id.name), bind.name.span)),
+            _ => None,
+        })
+        .next()
+}
+
+/// Returns whether `expr` spans exactly `span` or is a record with `span` as the value of one of
+/// its fields, possibly within nested records
+fn is_field_value(expr: &SpannedExpr<Symbol>, span: Span<BytePos>) -> bool {
+    expr.span == span ||
+    match expr.value {
+        Expr::Record { ref exprs, .. } => {
+            exprs.iter()
+                .filter_map(|&(_, ref value)| value.as_ref())
+                .any(|value| is_field_value(value, span))
+        }
+        _ => false,
+    }
+}
+
+/// Returns the innermost expression which spans exactly `span`
+fn expr_with_span(expr: &SpannedExpr<Symbol>,
+                  span: Span<BytePos>)
+                  -> Option<&SpannedExpr<Symbol>> {
+    let inner = query::sub_exprs(expr)
+        .into_iter()
+        .filter_map(|sub_expr| expr_with_span(sub_expr, span))
+        .next();
+    if inner.is_none() && expr.span == span {
+        Some(expr)
+    } else {
+        inner
+    }
+}
@@ -0,0 +1,154 @@
+use std::fs::File;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+use gluon::base::fnv::FnvMap;
+
+use url::Url;
+
+use languageserver_types::TextDocumentContentChangeEvent;
+
+use position::{PositionEncoding, position_to_offset};
+
+/// The contents of a document as last reported by the client
+#[derive(Clone, Debug)]
+pub struct Document {
+    pub version: u64,
+    pub language_id: Option<String>,
+    pub text: String,
+}
+
+/// Tracks the documents which are open in the client, keyed by their uri
+#[derive(Clone)]
+pub struct DocumentStore(pub Arc<Mutex<FnvMap<String, Document>>>);
+
+impl DocumentStore {
+    pub fn new() -> DocumentStore {
+        DocumentStore(Arc::new(Mutex::new(FnvMap::default())))
+    }
+
+    pub fn open(&self, uri: &str, version: u64, language_id: Option<String>, text: String) {
+        self.0.lock().unwrap().insert(uri.into(),
+                                      Document {
+                                          version: version,
+                                          language_id: language_id,
+                                          text: text,
+                                      });
+    }
+
+    /// Stops tracking the document at `uri`, returning it if it was open
+    pub fn close(&self, uri: &str) -> Option<Document> {
+        self.0.lock().unwrap().remove(uri)
+    }
+
+    /// Returns true if the document at `uri` is known to be something other than gluon source
+    pub fn is_foreign(&self, uri: &str) -> bool {
+        self.get(uri)
+            .and_then(|document| document.language_id)
+            .map_or(false, |language_id| language_id != "gluon")
+    }
+
+    /// Returns the contents of the document at `uri`, reading it from disk if it is not open in
+    /// the client
+    pub fn contents(&self, uri: &str) -> Result<String, String> {
+        if let Some(document) = self.get(uri) {
+            return Ok(document.text);
+        }
+        let path = try!(Url::parse(uri)
+            .ok()
+            .and_then(|url| url.to_file_path().ok())
+            .ok_or_else(|| format!("`{}` does not refer to a file", uri)));
+        let mut text = String::new();
+        try!(File::open(&path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|err| format!("Unable to read `{}`: {}", path.display(), err)));
+        Ok(text)
+    }
+
+    /// Applies `changes` in order to the document at `uri` and returns the updated text.
+    ///
+    /// Changes which are not newer than the stored version of the document are rejected.
+    pub fn change(&self,
+                  uri: &str,
+                  version: u64,
+                  changes: &[TextDocumentContentChangeEvent],
+                  encoding: PositionEncoding)
+                  -> Result<String, String> {
+        let mut documents = self.0.lock().unwrap();
+        if !documents.contains_key(uri) {
+            // Without a previous version of the document only full replacements can be applied
+            return match changes.iter().rev().find(|change| change.range.is_none()) {
+                Some(change) if changes.iter().all(|change| change.range.is_none()) => {
+                    documents.insert(uri.into(),
+                                     Document {
+                                         version: version,
+                                         language_id: None,
+                                         text: change.text.clone(),
+                                     });
+                    Ok(change.text.clone())
+                }
+                _ => Err(format!("Received a change for the unknown document `{}`", uri)),
+            };
+        }
+        let document = documents.get_mut(uri).unwrap();
+        if version <= document.version {
+            return Err(format!("Received version {} of `{}` which is not newer than the \
+                                current version {}",
+                               version,
+                               uri,
+                               document.version));
+        }
+        if version != document.version + 1 {
+            debug!("Expected version {} of `{}`, got {}",
+                   document.version + 1,
+                   uri,
+                   version);
+        }
+        let mut text = document.text.clone();
+        for change in changes {
+            try!(apply_change(&mut text, change, encoding));
+        }
+        document.version = version;
+        document.text = text.clone();
+        Ok(text)
+    }
+
+    pub fn get(&self, uri: &str) -> Option<Document> {
+        self.0.lock().unwrap().get(uri).cloned()
+    }
+
+    /// Returns a snapshot of every open document
+    pub fn documents(&self) -> Vec<(String, Document)> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(uri, document)| (uri.clone(), document.clone()))
+            .collect()
+    }
+}
+
+fn apply_change(text: &mut String,
+                change: &TextDocumentContentChangeEvent,
+                encoding: PositionEncoding)
+                -> Result<(), String> {
+    match change.range {
+        Some(ref range) => {
+            let start = try!(position_to_offset(text, &range.start, encoding)
+                .ok_or_else(|| format!("Invalid start position {:?}", range.start)));
+            let end = try!(position_to_offset(text, &range.end, encoding)
+                .ok_or_else(|| format!("Invalid end position {:?}", range.end)));
+            if end < start {
+                return Err(format!("Invalid range {:?}", range));
+            }
+            let mut new_text = String::with_capacity(text.len() - (end - start) +
+                                                     change.text.len());
+            new_text.push_str(&text[..start]);
+            new_text.push_str(&change.text);
+            new_text.push_str(&text[end..]);
+            *text = new_text;
+        }
+        None => *text = change.text.clone(),
+    }
+    Ok(())
+}
@@ -25,9 +25,7 @@ export function activate(context: ExtensionContext) {
 		documentSelector: ['gluon'],
 		synchronize: {
 			// Synchronize the setting section 'languageServerExample' to the server
-			configurationSection: 'gluon',
-			// Notify the server about file changes to '.clientrc files contain in the workspace
-			fileEvents: workspace.createFileSystemWatcher('**/.clientrc')
+			configurationSection: 'gluon'
 		}
 	}
 	
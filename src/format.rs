@@ -0,0 +1,1293 @@
+use std::cmp;
+use std::iter;
+
+use gluon::base::ast::{is_operator_char, Expr, Lambda, Literal, Pattern, SpannedExpr, TypeBinding,
+                       ValueBinding};
+use gluon::base::pos::BytePos;
+use gluon::base::symbol::Symbol;
+use gluon::base::types::{ArcType, BuiltinType, Field, Type};
+
+use languageserver_types::{FormattingOptions, Position, Range, TextEdit};
+
+use position::PositionEncoding;
+use query;
+
+/// Lines are broken so that they do not exceed this many columns where possible
+const WIDTH: usize = 100;
+
+/// Pretty-prints `expr`, which was parsed from `source`, in the canonical layout.
+///
+/// Comments written after code stay at the end of the line which that code is printed on and
+/// comments on lines of their own are kept above the code following them. A single blank line is
+/// kept wherever the source has one or more between two statements, fields or alternatives. Lines
+/// end with `\r\n` if the source uses them.
+pub fn pretty(source: &str, expr: &SpannedExpr<Symbol>, options: &FormattingOptions) -> String {
+    let mut printer = Printer::new(source, options);
+    let start = printer.start(expr);
+    printer.newline(0, Some(start));
+    printer.block(expr, 0);
+    printer.newline(0, Some(BytePos::from(source.len())));
+    let mut out = printer.out.trim_right().to_string();
+    out.push('\n');
+    if source.contains("\r\n") {
+        out = out.replace("\r\n", "\n").replace('\n', "\r\n");
+    }
+    out
+}
+
+/// Returns the edits which turn `old` into `new`. Only the lines which differ are replaced and
+/// lines which only differ in their indentation or the end of the line are edited in place. If
+/// `range` is given only edits within its lines are returned. The edits count characters in
+/// `encoding`.
+pub fn edits(old: &str,
+             new: &str,
+             range: Option<&Range>,
+             encoding: PositionEncoding)
+             -> Vec<TextEdit> {
+    let old_lines = lines(old);
+    let new_lines = lines(new);
+    let old_starts = line_starts(&old_lines);
+    let new_starts = line_starts(&new_lines);
+
+    let mut edits = Vec::new();
+    for (old_range, new_range) in hunks(&old_lines, &new_lines) {
+        if old_range.1 - old_range.0 == new_range.1 - new_range.0 {
+            for (i, j) in (old_range.0..old_range.1).zip(new_range.0..new_range.1) {
+                let (from, to) = (old_lines[i], new_lines[j]);
+                let prefix = from.char_indices()
+                    .zip(to.chars())
+                    .find(|&((_, a), b)| a != b)
+                    .map_or(cmp::min(from.len(), to.len()), |((i, _), _)| i);
+                let suffix = from[prefix..]
+                    .chars()
+                    .rev()
+                    .zip(to[prefix..].chars().rev())
+                    .take_while(|&(a, b)| a == b)
+                    .map(|(a, _)| a.len_utf8())
+                    .sum::<usize>();
+                edits.push(edit(old,
+                                &old_starts,
+                                old_starts[i] + prefix,
+                                old_starts[i] + from.len() - suffix,
+                                &to[prefix..to.len() - suffix],
+                                encoding));
+            }
+        } else {
+            edits.push(edit(old,
+                            &old_starts,
+                            old_starts[old_range.0],
+                            old_starts[old_range.1],
+                            &new[new_starts[new_range.0]..new_starts[new_range.1]],
+                            encoding));
+        }
+    }
+    if let Some(range) = range {
+        edits.retain(|edit| {
+            let end = &edit.range.end;
+            range.start.line <= edit.range.start.line &&
+            (end.line <= range.end.line || end.line == range.end.line + 1 && end.character == 0)
+        });
+    }
+    edits
+}
+
+/// Splits `text` into lines, each including its line ending
+fn lines(text: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, _) in text.match_indices('\n') {
+        lines.push(&text[start..i + 1]);
+        start = i + 1;
+    }
+    if start < text.len() {
+        lines.push(&text[start..]);
+    }
+    lines
+}
+
+/// Returns the offset of each line in `lines` followed by the length of the text
+fn line_starts(lines: &[&str]) -> Vec<usize> {
+    let mut starts = vec![0];
+    for line in lines {
+        let end = starts[starts.len() - 1] + line.len();
+        starts.push(end);
+    }
+    starts
+}
+
+/// Creates an edit replacing `start..end` of `text`, whose lines start at `line_starts`
+fn edit(text: &str,
+        line_starts: &[usize],
+        start: usize,
+        end: usize,
+        new_text: &str,
+        encoding: PositionEncoding)
+        -> TextEdit {
+    let position = |offset: usize| {
+        let line = match line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+        Position {
+            line: line as u64,
+            character: encoding.code_units(&text[line_starts[line]..offset]) as u64,
+        }
+    };
+    TextEdit {
+        range: Range {
+            start: position(start),
+            end: position(end),
+        },
+        new_text: new_text.into(),
+    }
+}
+
+/// Returns the ranges of lines in `old` which are replaced by ranges of lines in `new`
+fn hunks(old: &[&str], new: &[&str]) -> Vec<((usize, usize), (usize, usize))> {
+    // Lines which are the same at the start and end are skipped so that only the changed part of
+    // the document needs to be compared line by line
+    let prefix = old.iter().zip(new).take_while(|&(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|&(a, b)| a == b)
+        .count();
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    let (rows, columns) = (old_end - prefix, new_end - prefix);
+    if rows == 0 && columns == 0 {
+        return Vec::new();
+    }
+    if rows * columns > 4_000_000 {
+        return vec![((prefix, old_end), (prefix, new_end))];
+    }
+
+    // `common[i][j]` is the length of the longest common subsequence of the lines after `i` in
+    // `old` and after `j` in `new`
+    let mut common = vec![vec![0u32; columns + 1]; rows + 1];
+    for i in (0..rows).rev() {
+        for j in (0..columns).rev() {
+            common[i][j] = if old[prefix + i] == new[prefix + j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                cmp::max(common[i + 1][j], common[i][j + 1])
+            };
+        }
+    }
+
+    let mut hunks = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let mut hunk_start = None;
+    while i < rows || j < columns {
+        if i < rows && j < columns && old[prefix + i] == new[prefix + j] {
+            if let Some((start_i, start_j)) = hunk_start.take() {
+                hunks.push(((prefix + start_i, prefix + i), (prefix + start_j, prefix + j)));
+            }
+            i += 1;
+            j += 1;
+            continue;
+        }
+        if hunk_start.is_none() {
+            hunk_start = Some((i, j));
+        }
+        if j == columns || i < rows && common[i + 1][j] >= common[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    if let Some((start_i, start_j)) = hunk_start {
+        hunks.push(((prefix + start_i, prefix + rows), (prefix + start_j, prefix + columns)));
+    }
+    hunks
+}
+
+/// A `//` or `/* */` comment. Documentation comments are stored in the bindings they document.
+struct Comment<'a> {
+    start: usize,
+    end: usize,
+    text: &'a str,
+    /// The start of the line the comment is on if it is written after code on that line
+    trailing: Option<usize>,
+}
+
+fn comments<'a>(source: &'a str, spans: &[(usize, usize)]) -> Vec<Comment<'a>> {
+    spans.iter()
+        .filter_map(|&(start, end)| {
+            let text = source[start..end].trim_right();
+            let is_comment = text.starts_with("//") && !text.starts_with("///") ||
+                             text.starts_with("/*") && !text.starts_with("/**");
+            if !is_comment {
+                return None;
+            }
+            let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+            Some(Comment {
+                start: start,
+                end: start + text.len(),
+                text: text,
+                trailing: if source[line_start..start].trim().is_empty() {
+                    None
+                } else {
+                    Some(line_start)
+                },
+            })
+        })
+        .collect()
+}
+
+struct Printer<'a> {
+    source: &'a str,
+    /// The spans of every comment, including documentation comments, and string literal which
+    /// are skipped when searching the source for keywords
+    skipped: Vec<(usize, usize)>,
+    comments: Vec<Comment<'a>>,
+    /// The first comment which has not been written yet
+    next_comment: usize,
+    /// The end of the code in `source` which has been written so far
+    source_pos: usize,
+    indent_unit: String,
+    tab_size: usize,
+    out: String,
+}
+
+impl<'a> Printer<'a> {
+    fn new(source: &'a str, options: &FormattingOptions) -> Printer<'a> {
+        let tab_size = cmp::max(options.tab_size, 1) as usize;
+        let skipped = query::comments_and_strings(source, true);
+        Printer {
+            source: source,
+            comments: comments(source, &skipped),
+            skipped: skipped,
+            next_comment: 0,
+            source_pos: 0,
+            indent_unit: if options.insert_spaces {
+                " ".repeat(tab_size)
+            } else {
+                "\t".to_string()
+            },
+            tab_size: tab_size,
+            out: String::new(),
+        }
+    }
+
+    fn column(&self) -> usize {
+        let line = &self.out[self.out.rfind('\n').map_or(0, |i| i + 1)..];
+        line.chars().map(|c| if c == '\t' { self.tab_size } else { 1 }).sum()
+    }
+
+    fn fits(&self, text: &str) -> bool {
+        self.column() + text.chars().count() <= WIDTH
+    }
+
+    fn write(&mut self, text: &str) {
+        self.out.push_str(text);
+    }
+
+    /// Writes `text` which was printed from the code in `source` ending at `end`
+    fn code(&mut self, text: &str, end: usize) {
+        self.out.push_str(text);
+        self.source_pos = cmp::max(self.source_pos, end);
+    }
+
+    /// Starts a new line indented `indent` levels. Comments after the code written so far are
+    /// written at the end of the current line and, if `next` is given, comments before it are
+    /// written on their own lines.
+    fn newline(&mut self, indent: usize, next: Option<BytePos>) {
+        while self.next_comment < self.comments.len() {
+            let (text, end) = {
+                let comment = &self.comments[self.next_comment];
+                let before_next = next.map_or(true, |next| comment.start < next.to_usize());
+                match comment.trailing {
+                    Some(line_start) if line_start < self.source_pos && before_next => {
+                        (comment.text, comment.end)
+                    }
+                    _ => break,
+                }
+            };
+            self.out.push(' ');
+            self.out.push_str(text);
+            self.source_pos = cmp::max(self.source_pos, end);
+            self.next_comment += 1;
+        }
+        if let Some(next) = next {
+            let next = next.to_usize();
+            while self.next_comment < self.comments.len() &&
+                  self.comments[self.next_comment].start < next {
+                let (start, end, text) = {
+                    let comment = &self.comments[self.next_comment];
+                    (comment.start, comment.end, comment.text)
+                };
+                self.line_break(indent, Some(start));
+                self.out.push_str(text);
+                self.source_pos = cmp::max(self.source_pos, end);
+                self.next_comment += 1;
+            }
+            self.line_break(indent, Some(next));
+        } else {
+            self.line_break(indent, None);
+        }
+    }
+
+    fn line_break(&mut self, indent: usize, next: Option<usize>) {
+        if !self.out.is_empty() {
+            if let Some(next) = next {
+                if self.source_pos < next {
+                    // Keep a single blank line wherever the source has any
+                    let between: Vec<_> = self.source[self.source_pos..next].split('\n').collect();
+                    if between.len() > 2 &&
+                       between[1..between.len() - 1].iter().any(|line| line.trim().is_empty()) {
+                        self.out.push('\n');
+                    }
+                }
+            }
+            self.out.push('\n');
+        }
+        for _ in 0..indent {
+            self.out.push_str(&self.indent_unit);
+        }
+    }
+
+    /// Returns the position of the next `keyword` after the code written so far
+    fn find_keyword(&self, keyword: &str) -> Option<usize> {
+        let mut start = self.source_pos;
+        while let Some(found) = query::find_word(self.source, keyword, start, self.source.len()) {
+            if !self.skipped.iter().any(|&(skip_start, end)| skip_start <= found && found < end) {
+                return Some(found);
+            }
+            start = found + keyword.len();
+        }
+        None
+    }
+
+    /// Returns the position of the `let` or `and` keyword before the binding of `name`
+    fn keyword_before(&self, name: BytePos) -> BytePos {
+        let before = self.source[..name.to_usize()].trim_right();
+        if before.ends_with("let") || before.ends_with("and") {
+            BytePos::from(before.len() - 3)
+        } else {
+            name
+        }
+    }
+
+    /// Returns the position `expr` starts at. Bindings are only spanned from the first binding of
+    /// a chain of `let` and `type` bindings so their starts are looked up instead.
+    fn start(&self, expr: &SpannedExpr<Symbol>) -> BytePos {
+        match expr.value {
+            Expr::LetBindings(ref binds, _) => self.keyword_before(binds[0].name.span.start),
+            Expr::TypeBindings(..) => {
+                self.find_keyword("type").map_or(expr.span.start, BytePos::from)
+            }
+            _ => expr.span.start,
+        }
+    }
+
+    /// Returns `expr` printed on a single line, along with the end of the code it was printed
+    /// from, unless it must span several lines or has comments inside it
+    fn flat(&self, expr: &SpannedExpr<Symbol>) -> Option<(String, usize)> {
+        self.flat_parts(&[("", expr, false)])
+    }
+
+    /// Returns `parts` printed on a single line, like `flat`
+    fn flat_parts(&self, parts: &[Part]) -> Option<(String, usize)> {
+        let mut out = String::new();
+        let mut extent = (usize::max_value(), 0);
+        if !self.flat_parts_into(parts, &mut out, &mut extent) {
+            return None;
+        }
+        let (start, end) = extent;
+        if self.comments[self.next_comment..]
+            .iter()
+            .any(|comment| start < comment.start && comment.start < end) {
+            return None;
+        }
+        Some((out, end))
+    }
+
+    /// Writes `expr` if it fits on the current line
+    fn try_flat(&mut self, expr: &SpannedExpr<Symbol>) -> bool {
+        match self.flat(expr) {
+            Some((text, end)) => {
+                if self.fits(&text) {
+                    self.code(&text, end);
+                    true
+                } else {
+                    false
+                }
+            }
+            None => false,
+        }
+    }
+
+    /// Writes `expr` at the start of a line indented `indent` levels
+    fn block(&mut self, expr: &SpannedExpr<Symbol>, indent: usize) {
+        let mut expr = expr;
+        loop {
+            match expr.value {
+                Expr::LetBindings(ref binds, ref body) => {
+                    for (i, bind) in binds.iter().enumerate() {
+                        if i != 0 {
+                            let next = self.keyword_before(bind.name.span.start);
+                            self.newline(indent, Some(next));
+                        }
+                        self.value_binding(bind, if i == 0 { "let " } else { "and " }, indent);
+                    }
+                    let next = self.start(body);
+                    self.newline(indent, Some(next));
+                    expr = body;
+                }
+                Expr::TypeBindings(ref binds, ref body) => {
+                    for (i, bind) in binds.iter().enumerate() {
+                        let keyword = if i == 0 { "type" } else { "and" };
+                        let found = self.find_keyword(keyword);
+                        if i != 0 {
+                            self.newline(indent, found.map(BytePos::from));
+                        }
+                        if let Some(found) = found {
+                            self.source_pos = cmp::max(self.source_pos, found + keyword.len());
+                        }
+                        self.type_binding(bind, if i == 0 { "type " } else { "and " }, indent);
+                    }
+                    // Type bindings have no spans so skip to the end of the code before the body
+                    let next = self.start(body);
+                    self.source_pos = cmp::max(self.source_pos, self.code_end(next.to_usize()));
+                    self.newline(indent, Some(next));
+                    expr = body;
+                }
+                _ => break,
+            }
+        }
+        match expr.value {
+            Expr::Block(ref exprs) => {
+                for (i, expr) in exprs.iter().enumerate() {
+                    if i != 0 {
+                        let next = self.start(expr);
+                        self.newline(indent, Some(next));
+                    }
+                    self.block(expr, indent);
+                }
+            }
+            Expr::Match(ref scrutinee, ref alts) => {
+                self.write("match ");
+                self.inline(scrutinee, indent);
+                self.write(" with");
+                for alt in alts {
+                    self.newline(indent, Some(alt.pattern.span.start));
+                    let text = format!("| {} ->", pattern(&alt.pattern.value));
+                    self.code(&text, alt.pattern.span.end.to_usize());
+                    self.value(&alt.expr, indent);
+                }
+            }
+            _ => self.expr(expr, indent),
+        }
+    }
+
+    /// Returns the end of the code before `pos`, skipping whitespace and comments
+    fn code_end(&self, pos: usize) -> usize {
+        let mut end = self.source[..pos].trim_right().len();
+        // Documentation comments are included as they belong to the code after them
+        while let Some(&(start, _)) = self.skipped
+            .iter()
+            .find(|&&(start, comment_end)| {
+                self.source[start..].starts_with('/') &&
+                start + self.source[start..comment_end].trim_right().len() == end
+            }) {
+            end = self.source[..start].trim_right().len();
+        }
+        end
+    }
+
+    fn doc_comment(&mut self, comment: &str, indent: usize) {
+        // The parser removes the whitespace at the start of each `///` comment so those can only
+        // be used if no line starts with whitespace
+        let lines: Vec<_> = comment.split('\n').collect();
+        if lines.iter().all(|line| !line.is_empty() && line.trim() == *line) {
+            for line in lines {
+                self.write("/// ");
+                self.write(line);
+                self.line_break(indent, None);
+            }
+        } else {
+            self.write("/** ");
+            self.write(comment);
+            self.write("*/");
+            self.line_break(indent, None);
+        }
+    }
+
+    fn value_binding(&mut self, bind: &ValueBinding<Symbol>, keyword: &str, indent: usize) {
+        if let Some(ref comment) = bind.comment {
+            self.doc_comment(comment, indent);
+        }
+        let mut head = String::from(keyword);
+        head.push_str(&pattern(&bind.name.value));
+        for arg in &bind.args {
+            head.push(' ');
+            head.push_str(&ident(&arg.name));
+        }
+        if let Type::Hole = *bind.typ {
+        } else {
+            head.push_str(" : ");
+            head.push_str(&typ(&bind.typ, Prec::Top));
+        }
+        head.push_str(" =");
+        self.code(&head, bind.name.span.end.to_usize());
+        self.value(&bind.expr, indent);
+    }
+
+    fn type_binding(&mut self, bind: &TypeBinding<Symbol>, keyword: &str, indent: usize) {
+        if let Some(ref comment) = bind.comment {
+            self.doc_comment(comment, indent);
+        }
+        let mut head = String::from(keyword);
+        head.push_str(&ident(&bind.name));
+        for arg in &bind.alias.args {
+            head.push(' ');
+            head.push_str(&ident(&arg.id));
+        }
+        head.push_str(" =");
+        self.write(&head);
+
+        let rhs = typ(&bind.alias.typ, Prec::Top);
+        let is_variant = match *bind.alias.typ {
+            Type::Variant(_) => true,
+            _ => false,
+        };
+        if !is_variant && self.fits(&format!(" {}", rhs)) {
+            self.write(" ");
+            self.write(&rhs);
+            return;
+        }
+        match *bind.alias.typ {
+            Type::Variant(ref row) => {
+                for variant in row_fields(row) {
+                    self.newline(indent + 1, None);
+                    self.write(&format!("| {}", variant_type(variant)));
+                }
+            }
+            Type::Record(ref row) => {
+                self.write(" {");
+                let fields = record_type_fields(row);
+                for (i, field) in fields.iter().enumerate() {
+                    self.newline(indent + 1, None);
+                    self.write(field);
+                    if i + 1 != fields.len() {
+                        self.write(",");
+                    }
+                }
+                self.newline(indent, None);
+                self.write("}");
+            }
+            _ => {
+                self.write(" ");
+                self.write(&rhs);
+            }
+        }
+    }
+
+    /// Writes `expr` after a `=` or `->`, on the same line if it fits
+    fn value(&mut self, expr: &SpannedExpr<Symbol>, indent: usize) {
+        // Comments between the `=` and the value are kept above the value
+        let start = self.start(expr);
+        let comment_before = self.comments
+            .get(self.next_comment)
+            .map_or(false, |comment| comment.start < start.to_usize());
+        if comment_before {
+            self.newline(indent + 1, Some(start));
+            self.block(expr, indent + 1);
+            return;
+        }
+        if let Some((text, end)) = self.flat(expr) {
+            if self.column() + 1 + text.chars().count() <= WIDTH {
+                self.write(" ");
+                self.code(&text, end);
+                return;
+            }
+        }
+        match expr.value {
+            Expr::Lambda(..) |
+            Expr::Record { .. } |
+            Expr::Array(..) => {
+                self.write(" ");
+                self.expr(expr, indent);
+            }
+            _ => {
+                self.newline(indent + 1, Some(start));
+                self.block(expr, indent + 1);
+            }
+        }
+    }
+
+    /// Writes `expr` where it can be printed without being wrapped in parentheses, on the current
+    /// line if it fits
+    fn expr(&mut self, expr: &SpannedExpr<Symbol>, indent: usize) {
+        if self.try_flat(expr) {
+            return;
+        }
+        match expr.value {
+            Expr::App(ref func, ref args) => {
+                if self.hang_last(&app_parts(func, args), indent) {
+                    return;
+                }
+                self.arg(func, indent);
+                for arg in args {
+                    self.newline(indent + 1, Some(arg.span.start));
+                    self.arg(arg, indent + 1);
+                }
+            }
+            Expr::Infix(..) => {
+                let mut operands = Vec::new();
+                infix_operands(expr, "", &mut operands);
+                if self.hang_last(&operands, indent) {
+                    return;
+                }
+                for (i, &(op, operand, parens)) in operands.iter().enumerate() {
+                    if i != 0 {
+                        let start = self.start(operand);
+                        self.newline(indent + 1, Some(start));
+                        self.write(op);
+                        self.write(" ");
+                    }
+                    let indent = if i == 0 { indent } else { indent + 1 };
+                    if parens {
+                        self.parens(operand, indent);
+                    } else {
+                        self.expr(operand, indent);
+                    }
+                }
+            }
+            Expr::Lambda(ref lambda) => {
+                self.write(&lambda_head(lambda));
+                self.value(&lambda.body, indent);
+            }
+            Expr::IfElse(..) => {
+                let mut expr = expr;
+                while let Expr::IfElse(ref pred, ref if_true, ref if_false) = expr.value {
+                    self.write("if ");
+                    self.inline(pred, indent);
+                    self.write(" then");
+                    let start = self.start(if_true);
+                    self.newline(indent + 1, Some(start));
+                    self.block(if_true, indent + 1);
+                    let start = self.start(if_false);
+                    self.newline(indent, Some(start));
+                    self.write("else");
+                    match if_false.value {
+                        Expr::IfElse(..) => {
+                            self.write(" ");
+                            expr = if_false;
+                        }
+                        _ => {
+                            self.newline(indent + 1, Some(start));
+                            self.block(if_false, indent + 1);
+                            break;
+                        }
+                    }
+                }
+            }
+            Expr::Record { .. } => {
+                self.code("{", expr.span.start.to_usize() + 1);
+                let fields = self.record_fields(expr);
+                for (i, &(start, ref field)) in fields.iter().enumerate() {
+                    self.newline(indent + 1, Some(BytePos::from(start)));
+                    match *field {
+                        RecordField::Type(name, typ_field) => {
+                            let name = ident(name);
+                            self.code(&name, start + name.len());
+                            if let Some(typ_field) = typ_field {
+                                self.write(" = ");
+                                self.write(&typ(typ_field, Prec::Top));
+                            }
+                        }
+                        RecordField::Value(name, value) => {
+                            let name = ident(name);
+                            match value {
+                                Some(value) => {
+                                    self.write(&name);
+                                    self.write(" =");
+                                    self.value(value, indent + 1);
+                                }
+                                None => self.code(&name, start + name.len()),
+                            }
+                        }
+                    }
+                    if i + 1 != fields.len() {
+                        self.write(",");
+                    }
+                }
+                self.newline(indent, None);
+                self.write("}");
+            }
+            Expr::Array(ref array) => {
+                self.code("[", expr.span.start.to_usize() + 1);
+                for (i, element) in array.exprs.iter().enumerate() {
+                    let start = self.start(element);
+                    self.newline(indent + 1, Some(start));
+                    self.block(element, indent + 1);
+                    if i + 1 != array.exprs.len() {
+                        self.write(",");
+                    }
+                }
+                self.newline(indent, None);
+                self.write("]");
+            }
+            Expr::Projection(ref base, ref field, _) => {
+                if needs_parens_projected(base) {
+                    self.parens(base, indent);
+                } else {
+                    self.expr(base, indent);
+                }
+                self.code(&format!(".{}", ident(field)), expr.span.end.to_usize());
+            }
+            Expr::LetBindings(..) |
+            Expr::TypeBindings(..) |
+            Expr::Match(..) |
+            Expr::Block(..) => self.parens(expr, indent),
+            Expr::Ident(..) |
+            Expr::Literal(..) |
+            Expr::Tuple(..) => {
+                // Always fits on one line
+                let (text, end) = self.flat(expr).expect("Flat expression");
+                self.code(&text, end);
+            }
+        }
+    }
+
+    /// Writes `parts` on the current line with the last one, if it is a lambda, record or array,
+    /// continuing on the following lines. Returns false if that does not fit.
+    fn hang_last(&mut self, parts: &[Part], indent: usize) -> bool {
+        let (&(op, last, parens), init) = match parts.split_last() {
+            Some(split) => split,
+            None => return false,
+        };
+        let open = match last.value {
+            Expr::Lambda(ref lambda) => lambda_head(lambda),
+            Expr::Record { .. } => "{".to_string(),
+            Expr::Array(..) => "[".to_string(),
+            _ => return false,
+        };
+        let (text, end) = match self.flat_parts(init) {
+            Some(flat) => flat,
+            None => return false,
+        };
+        let text = text + &separator(op);
+        if !self.fits(&format!("{}{}{}", text, if parens { "(" } else { "" }, open)) {
+            return false;
+        }
+        self.code(&text, end);
+        if parens {
+            self.parens(last, indent);
+        } else {
+            self.expr(last, indent);
+        }
+        true
+    }
+
+    /// Writes `expr` as an argument of a function application
+    fn arg(&mut self, expr: &SpannedExpr<Symbol>, indent: usize) {
+        if needs_parens_arg(expr) {
+            self.parens(expr, indent);
+        } else {
+            self.expr(expr, indent);
+        }
+    }
+
+    /// Writes `expr` on a single line if possible, regardless of its length. Used where the syntax
+    /// expects the expression to be short, such as the condition of an `if`.
+    fn inline(&mut self, expr: &SpannedExpr<Symbol>, indent: usize) {
+        match self.flat(expr) {
+            Some((text, end)) => self.code(&text, end),
+            None => self.parens(expr, indent),
+        }
+    }
+
+    fn parens(&mut self, expr: &SpannedExpr<Symbol>, indent: usize) {
+        if let Some((text, end)) = self.flat(expr) {
+            let text = format!("({})", text);
+            if self.fits(&text) {
+                self.code(&text, end);
+                return;
+            }
+        }
+        self.write("(");
+        match expr.value {
+            Expr::LetBindings(..) |
+            Expr::TypeBindings(..) |
+            Expr::Match(..) |
+            Expr::Block(..) |
+            Expr::IfElse(..) => {
+                let start = self.start(expr);
+                self.newline(indent + 1, Some(start));
+                self.block(expr, indent + 1);
+                self.newline(indent, None);
+            }
+            _ => self.expr(expr, indent),
+        }
+        self.write(")");
+    }
+
+    /// Writes `expr` on a single line to `out`, extending `extent` with the code it spans. Returns
+    /// false if `expr` must span several lines.
+    fn flat_into(&self,
+                     expr: &SpannedExpr<Symbol>,
+                     out: &mut String,
+                     extent: &mut (usize, usize))
+                     -> bool {
+        extent.0 = cmp::min(extent.0, expr.span.start.to_usize());
+        extent.1 = cmp::max(extent.1, expr.span.end.to_usize());
+        match expr.value {
+            Expr::Ident(ref id) => out.push_str(&ident(&id.name)),
+            Expr::Literal(ref lit) => out.push_str(&literal(lit)),
+            Expr::App(ref func, ref args) => {
+                return self.flat_parts_into(&app_parts(func, args), out, extent);
+            }
+            Expr::Infix(..) => {
+                let mut operands = Vec::new();
+                infix_operands(expr, "", &mut operands);
+                return self.flat_parts_into(&operands, out, extent);
+            }
+            Expr::Lambda(ref lambda) => {
+                out.push_str(&lambda_head(lambda));
+                out.push(' ');
+                return self.flat_into(&lambda.body, out, extent);
+            }
+            Expr::IfElse(ref pred, ref if_true, ref if_false) => {
+                out.push_str("if ");
+                if !self.flat_into(pred, out, extent) {
+                    return false;
+                }
+                out.push_str(" then ");
+                if !self.flat_into(if_true, out, extent) {
+                    return false;
+                }
+                out.push_str(" else ");
+                return self.flat_into(if_false, out, extent);
+            }
+            Expr::Projection(ref base, ref field, _) => {
+                if !self.flat_parts_into(&[("", base, needs_parens_projected(base))], out, extent) {
+                    return false;
+                }
+                out.push('.');
+                out.push_str(&ident(field));
+            }
+            Expr::Array(ref array) => {
+                out.push('[');
+                for (i, element) in array.exprs.iter().enumerate() {
+                    if i != 0 {
+                        out.push_str(", ");
+                    }
+                    if !self.flat_into(element, out, extent) {
+                        return false;
+                    }
+                }
+                out.push(']');
+            }
+            Expr::Record { .. } => {
+                let fields = self.record_fields(expr);
+                if fields.is_empty() {
+                    out.push_str("{}");
+                    return true;
+                }
+                out.push_str("{ ");
+                for (i, &(_, ref field)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        out.push_str(", ");
+                    }
+                    match *field {
+                        RecordField::Type(name, typ_field) => {
+                            out.push_str(&ident(name));
+                            if let Some(typ_field) = typ_field {
+                                out.push_str(" = ");
+                                out.push_str(&typ(typ_field, Prec::Top));
+                            }
+                        }
+                        RecordField::Value(name, value) => {
+                            out.push_str(&ident(name));
+                            if let Some(value) = value {
+                                out.push_str(" = ");
+                                if !self.flat_into(value, out, extent) {
+                                    return false;
+                                }
+                            }
+                        }
+                    }
+                }
+                out.push_str(" }");
+            }
+            Expr::Tuple(ref exprs) if exprs.is_empty() => out.push_str("()"),
+            Expr::Tuple(_) |
+            Expr::LetBindings(..) |
+            Expr::TypeBindings(..) |
+            Expr::Match(..) |
+            Expr::Block(..) => return false,
+        }
+        true
+    }
+
+    fn flat_parts_into(&self,
+                       parts: &[Part],
+                       out: &mut String,
+                       extent: &mut (usize, usize))
+                       -> bool {
+        for (i, &(op, expr, parens)) in parts.iter().enumerate() {
+            if i != 0 {
+                out.push_str(&separator(op));
+            }
+            if parens {
+                out.push('(');
+            }
+            if !self.flat_into(expr, out, extent) {
+                return false;
+            }
+            if parens {
+                out.push(')');
+            }
+        }
+        true
+    }
+
+    /// Returns the fields of the record `expr` in the order they are written in, along with the
+    /// position they start at. The parser separates the types from the values so the names of
+    /// types and of fields without a value are searched for in the source.
+    fn record_fields<'e>(&self, expr: &'e SpannedExpr<Symbol>) -> Vec<(usize, RecordField<'e>)> {
+        let (types, exprs) = match expr.value {
+            Expr::Record { ref types, ref exprs, .. } => (types, exprs),
+            _ => return Vec::new(),
+        };
+        let start = expr.span.start.to_usize();
+        let values: Vec<_> = exprs.iter()
+            .filter_map(|&(_, ref value)| value.as_ref())
+            .filter(|value| !query::is_punned(expr.span, value))
+            .map(|value| {
+                let span = query::full_span(self.source, value);
+                (span.start.to_usize(), span.end.to_usize())
+            })
+            .collect();
+        let find = |name: &Symbol| {
+            let name: &str = name.as_ref();
+            let mut from = start;
+            while let Some(found) = query::find_word(self.source, name, from, self.source.len()) {
+                let skip = |&(skip_start, end): &(usize, usize)| skip_start <= found && found < end;
+                if !values.iter().any(&skip) && !self.skipped.iter().any(&skip) {
+                    return found;
+                }
+                from = found + name.len();
+            }
+            start
+        };
+        let mut fields: Vec<_> = types.iter()
+            .map(|&(ref name, ref typ)| (find(name), RecordField::Type(name, typ.as_ref())))
+            .chain(exprs.iter().map(|&(ref name, ref value)| {
+                match *value {
+                    Some(ref value) if !query::is_punned(expr.span, value) => {
+                        (value.span.start.to_usize(), RecordField::Value(name, Some(value)))
+                    }
+                    _ => (find(name), RecordField::Value(name, None)),
+                }
+            }))
+            .collect();
+        fields.sort_by_key(|&(start, _)| start);
+        fields
+    }
+}
+
+/// An operand of an application or infix expression along with the operator before it, if any,
+/// and whether it needs parentheses
+type Part<'e> = (&'e str, &'e SpannedExpr<Symbol>, bool);
+
+fn app_parts<'e>(func: &'e SpannedExpr<Symbol>, args: &'e [SpannedExpr<Symbol>]) -> Vec<Part<'e>> {
+    iter::once(func)
+        .chain(args)
+        .map(|expr| ("", expr, needs_parens_arg(expr)))
+        .collect()
+}
+
+fn separator(op: &str) -> String {
+    if op.is_empty() {
+        " ".to_string()
+    } else {
+        format!(" {} ", op)
+    }
+}
+
+enum RecordField<'e> {
+    Type(&'e Symbol, Option<&'e ArcType<Symbol>>),
+    /// A field and its value unless it was written without one
+    Value(&'e Symbol, Option<&'e SpannedExpr<Symbol>>),
+}
+
+fn lambda_head(lambda: &Lambda<Symbol>) -> String {
+    let mut head = String::from("\\");
+    for arg in &lambda.args {
+        head.push_str(&ident(&arg.name));
+        head.push(' ');
+    }
+    head.push_str("->");
+    head
+}
+
+fn needs_parens_arg(expr: &SpannedExpr<Symbol>) -> bool {
+    match expr.value {
+        Expr::Ident(..) |
+        Expr::Literal(..) |
+        Expr::Record { .. } |
+        Expr::Array(..) |
+        Expr::Tuple(..) |
+        Expr::Projection(..) => false,
+        _ => true,
+    }
+}
+
+fn needs_parens_projected(expr: &SpannedExpr<Symbol>) -> bool {
+    match expr.value {
+        // `1.x` would be read as a float
+        Expr::Literal(Literal::String(_)) => false,
+        _ => needs_parens_arg(expr),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Fixity {
+    Left,
+    Right,
+}
+
+/// Returns the precedence and fixity the parser gives to `op`
+fn operator(op: &str) -> (i32, Fixity) {
+    let name = if op.starts_with('#') {
+        op.trim_left_matches(|c: char| !is_operator_char(c))
+    } else {
+        op
+    };
+    match name {
+        "*" | "/" | "%" => (7, Fixity::Left),
+        "+" | "-" => (6, Fixity::Left),
+        ":" | "++" => (5, Fixity::Right),
+        "==" | "/=" | "<" | ">" | "<=" | ">=" => (4, Fixity::Left),
+        "&&" => (3, Fixity::Right),
+        "||" => (2, Fixity::Right),
+        "$" => (0, Fixity::Right),
+        "<|" => (0, Fixity::Right),
+        "|>" => (0, Fixity::Left),
+        "<<" => (9, Fixity::Right),
+        ">>" => (9, Fixity::Left),
+        _ => (9, Fixity::Left),
+    }
+}
+
+/// Flattens the operands of the infix expression `expr` which can be written without parentheses
+/// into `operands`
+fn infix_operands<'e>(expr: &'e SpannedExpr<Symbol>,
+                      op_before: &'e str,
+                      operands: &mut Vec<Part<'e>>) {
+    match expr.value {
+        Expr::Infix(ref lhs, ref op, ref rhs) => {
+            let op = op.name.as_ref();
+            let (precedence, fixity) = operator(op);
+            for &(operand, op_before, is_lhs) in &[(&**lhs, op_before, true),
+                                                   (&**rhs, op, false)] {
+                let flatten = match operand.value {
+                    Expr::Infix(_, ref inner, _) => {
+                        let (inner_precedence, inner_fixity) = operator(inner.name.as_ref());
+                        if inner_precedence != precedence {
+                            inner_precedence > precedence
+                        } else {
+                            inner_fixity == fixity && (fixity == Fixity::Left) == is_lhs
+                        }
+                    }
+                    _ => false,
+                };
+                if flatten {
+                    infix_operands(operand, op_before, operands);
+                } else {
+                    let parens = match operand.value {
+                        Expr::Ident(..) |
+                        Expr::Literal(..) |
+                        Expr::App(..) |
+                        Expr::Record { .. } |
+                        Expr::Array(..) |
+                        Expr::Tuple(..) |
+                        Expr::Projection(..) => false,
+                        _ => true,
+                    };
+                    operands.push((op_before, operand, parens));
+                }
+            }
+            // A lambda extends as far as possible so it can be the last operand of the
+            // outermost expression without parentheses
+            if op_before.is_empty() {
+                if let Some(last) = operands.last_mut() {
+                    if let Expr::Lambda(..) = last.1.value {
+                        last.2 = false;
+                    }
+                }
+            }
+        }
+        _ => operands.push((op_before, expr, false)),
+    }
+}
+
+fn ident(name: &Symbol) -> String {
+    let name: &str = name.as_ref();
+    if name.starts_with(|c: char| is_operator_char(c) || c == '#') {
+        format!("({})", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn pattern(pattern: &Pattern<Symbol>) -> String {
+    match *pattern {
+        Pattern::Ident(ref id) => ident(&id.name),
+        Pattern::Constructor(ref id, ref args) => {
+            iter::once(ident(&id.name))
+                .chain(args.iter().map(|arg| ident(&arg.name)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+        Pattern::Record { ref types, ref fields, .. } => {
+            let fields: Vec<_> = types.iter()
+                .chain(fields)
+                .map(|&(ref name, ref value)| {
+                    match *value {
+                        Some(ref value) => format!("{} = {}", ident(name), ident(value)),
+                        None => ident(name),
+                    }
+                })
+                .collect();
+            if fields.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{ {} }}", fields.join(", "))
+            }
+        }
+    }
+}
+
+fn literal(lit: &Literal) -> String {
+    fn escape(c: char, quote: char, out: &mut String) {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c == quote => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    match *lit {
+        Literal::Byte(b) => format!("{}b", b),
+        Literal::Int(i) => i.to_string(),
+        Literal::Float(f) => format!("{:?}", f),
+        Literal::String(ref s) => {
+            let mut out = String::from("\"");
+            for c in s.chars() {
+                escape(c, '"', &mut out);
+            }
+            out.push('"');
+            out
+        }
+        Literal::Char(c) => {
+            let mut out = String::from("'");
+            escape(c, '\'', &mut out);
+            out.push('\'');
+            out
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Prec {
+    Top,
+    Function,
+    Constructor,
+}
+
+fn enclose(parens: bool, text: String) -> String {
+    if parens { format!("({})", text) } else { text }
+}
+
+fn typ(typ: &ArcType<Symbol>, prec: Prec) -> String {
+    match **typ {
+        Type::Hole => "_".to_string(),
+        Type::Generic(ref generic) => ident(&generic.id),
+        Type::Builtin(BuiltinType::Function) => "(->)".to_string(),
+        Type::Builtin(builtin) => builtin.to_str().to_string(),
+        Type::Ident(ref id) => ident(id),
+        Type::Alias(ref alias) => ident(&alias.name),
+        Type::App(ref func, ref args) => {
+            match typ.as_function() {
+                Some((arg, ret)) => {
+                    enclose(prec >= Prec::Function,
+                            format!("{} -> {}",
+                                    self::typ(arg, Prec::Function),
+                                    self::typ(ret, Prec::Top)))
+                }
+                None => {
+                    let text = iter::once(func)
+                        .chain(args)
+                        .map(|typ| self::typ(typ, Prec::Constructor))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    enclose(prec >= Prec::Constructor, text)
+                }
+            }
+        }
+        Type::Record(ref row) => {
+            let fields = record_type_fields(row);
+            if fields.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{ {} }}", fields.join(", "))
+            }
+        }
+        Type::Variant(ref row) => {
+            row_fields(row)
+                .iter()
+                .map(|variant| format!("| {}", variant_type(variant)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+        _ => typ.to_string(),
+    }
+}
+
+fn row_fields(row: &ArcType<Symbol>) -> Vec<&Field<Symbol>> {
+    match **row {
+        Type::ExtendRow { ref fields, ref rest, .. } => {
+            let mut result: Vec<_> = fields.iter().collect();
+            result.extend(row_fields(rest));
+            result
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn record_type_fields(row: &ArcType<Symbol>) -> Vec<String> {
+    match **row {
+        Type::ExtendRow { ref types, ref fields, ref rest } => {
+            types.iter()
+                .map(|field| ident(&field.typ.name))
+                .chain(fields.iter().map(|field| {
+                    format!("{} : {}", ident(&field.name), typ(&field.typ, Prec::Top))
+                }))
+                .chain(record_type_fields(rest))
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Prints a constructor of a variant type, whose type is a function from its arguments to the
+/// variant type
+fn variant_type(constructor: &Field<Symbol>) -> String {
+    let mut args = vec![ident(&constructor.name)];
+    let mut typ = &constructor.typ;
+    while let Some((arg, ret)) = typ.as_function() {
+        args.push(self::typ(arg, Prec::Constructor));
+        typ = ret;
+    }
+    args.join(" ")
+}
@@ -0,0 +1,89 @@
+use gluon::base::ast::{Expr, Literal, SpannedExpr};
+use gluon::base::fnv::FnvMap;
+use gluon::base::symbol::Symbol;
+use gluon::filename_to_module;
+
+use query;
+
+/// Returns the names of the modules which `expr` imports with `import "<path>"`. `expr` must not
+/// have been expanded yet as expanding replaces each `import` with the module it refers to.
+pub fn imports(expr: &SpannedExpr<Symbol>) -> Vec<String> {
+    let mut imports = Vec::new();
+    let mut exprs = vec![expr];
+    while let Some(expr) = exprs.pop() {
+        if let Expr::App(ref func, ref args) = expr.value {
+            let path = args.first().and_then(|arg| match arg.value {
+                Expr::Literal(Literal::String(ref path)) => Some(path),
+                _ => None,
+            });
+            match (&func.value, path) {
+                (&Expr::Ident(ref id), Some(path)) if query::name(&id.name) == "import" => {
+                    imports.push(filename_to_module(path));
+                }
+                _ => (),
+            }
+        }
+        exprs.extend(query::sub_exprs(expr));
+    }
+    imports
+}
+
+/// Which modules each module imports, keyed by module name.
+///
+/// The imports of a module are replaced every time it is checked, whether it was opened or loaded
+/// through `import`, so removed imports do not cause extra checks.
+#[derive(Debug, Default)]
+pub struct ImportGraph {
+    imports: FnvMap<String, Vec<String>>,
+}
+
+impl ImportGraph {
+    /// Replaces the modules which `importer` imports with `imports`
+    pub fn set_imports(&mut self, importer: &str, mut imports: Vec<String>) {
+        imports.retain(|import| import != importer);
+        imports.sort();
+        imports.dedup();
+        self.imports.insert(importer.into(), imports);
+    }
+
+    /// Returns every module which depends on `module`, directly or indirectly, ordered so that each
+    /// module comes after the modules it imports
+    pub fn dependents(&self, module: &str) -> Vec<String> {
+        let mut dependents = vec![module.to_string()];
+        let mut i = 0;
+        while i < dependents.len() {
+            for (importer, imports) in &self.imports {
+                if imports.contains(&dependents[i]) && !dependents.contains(importer) {
+                    dependents.push(importer.clone());
+                }
+            }
+            i += 1;
+        }
+        dependents.remove(0);
+
+        let mut visited = Vec::new();
+        let mut ordered = Vec::new();
+        for dependent in &dependents {
+            self.visit(dependent, &dependents, &mut visited, &mut ordered);
+        }
+        ordered
+    }
+
+    /// Adds `module` to `ordered` after the modules in `dependents` which it imports
+    fn visit(&self,
+             module: &str,
+             dependents: &[String],
+             visited: &mut Vec<String>,
+             ordered: &mut Vec<String>) {
+        if visited.iter().any(|visited| visited == module) {
+            return;
+        }
+        visited.push(module.into());
+        for import in self.imports.get(module).into_iter().flat_map(|imports| imports) {
+            if dependents.contains(import) {
+                self.visit(import, dependents, visited, ordered);
+            }
+        }
+        ordered.push(module.into());
+    }
+}
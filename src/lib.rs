@@ -14,17 +14,35 @@ extern crate url;
 
 extern crate languageserver_types;
 
+pub mod cancellation;
+mod check;
+mod config;
+mod diagnostic;
+mod document;
+mod format;
+mod import_graph;
+mod lint;
+mod output;
+pub mod position;
+mod query;
+pub mod settings;
+mod symbols;
+mod transport;
+mod worker;
+mod workspace;
+
 use jsonrpc_core::{Error, ErrorCode, IoHandler, MethodCommand, MethodResult, NotificationCommand,
                    Params, Value};
 use serde_json::value::{from_value, to_value};
 
-use gluon::base::ast::SpannedExpr;
+use gluon::base::ast::{Expr, SpannedExpr};
+use gluon::base::error::Errors;
 use gluon::base::fnv::FnvMap;
 use gluon::base::metadata::Metadata;
-use gluon::base::pos::{self, BytePos, Line, Span};
-use gluon::base::source;
+use gluon::base::pos::{BytePos, Span};
 use gluon::base::symbol::Symbol;
 use gluon::check::completion;
+use gluon::check::typecheck::TypeError;
 use gluon::import::{Import, Importer};
 use gluon::vm::internal::Value as GluonValue;
 use gluon::vm::thread::{Thread, ThreadInternal};
@@ -32,22 +50,57 @@ use gluon::vm::macros::Error as MacroError;
 use gluon::{Compiler, Error as GluonError, Result as GluonResult, RootedThread, new_vm,
             filename_to_module};
 
+use std::cmp;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error as StdError;
+use std::fmt;
 use std::fs;
 use std::io;
-use std::io::{Read, BufRead, Write};
+use std::io::{Read, BufRead};
+use std::path::{Path, PathBuf};
 use std::str;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, mpsc};
 use std::sync::atomic;
 use std::sync::atomic::AtomicBool;
 
 use languageserver_types::*;
 
+pub use check::{CheckFormat, check_files};
+pub use config::{Config, Transport};
+
+use cancellation::{Cancellation, CancellationToken};
+use diagnostic::{RelatedInformation, Report};
+use document::DocumentStore;
+use import_graph::ImportGraph;
+use output::Output;
+use position::{Lines, PositionEncoding, SharedEncoding, byte_span_to_range};
+use settings::{Lints, Settings, SharedSettings};
+use worker::CheckWorker;
+use workspace::Workspaces;
+
+/// A typechecked module along with the source it was created from
+pub struct Module {
+    pub lines: Lines,
+    pub source: String,
+    pub expr: SpannedExpr<Symbol>,
+}
+
+impl Module {
+    fn new(source: &str, expr: SpannedExpr<Symbol>) -> Module {
+        Module {
+            lines: Lines::new(source),
+            source: source.into(),
+            expr: expr,
+        }
+    }
+}
+
 #[derive(Clone)]
-pub struct CheckImporter(pub Arc<Mutex<FnvMap<String, (source::Lines, SpannedExpr<Symbol>)>>>);
+pub struct CheckImporter(pub Arc<Mutex<FnvMap<String, Module>>>, Arc<Mutex<ImportGraph>>);
 impl CheckImporter {
     pub fn new() -> CheckImporter {
-        CheckImporter(Arc::new(Mutex::new(FnvMap::default())))
+        CheckImporter(Arc::new(Mutex::new(FnvMap::default())),
+                      Arc::new(Mutex::new(ImportGraph::default())))
     }
 }
 impl Importer for CheckImporter {
@@ -60,12 +113,22 @@ impl Importer for CheckImporter {
               -> Result<(), MacroError> {
         use gluon::compiler_pipeline::*;
 
+        // `expr` has already been expanded so the imports are found by parsing the module again
+        if let Ok(unexpanded) = compiler.parse_expr(module_name, input) {
+            self.1.lock().unwrap().set_imports(module_name, import_graph::imports(&unexpanded));
+        }
+
         let macro_value = MacroValue { expr: expr };
         let TypecheckValue { expr, typ } =
-            try!(macro_value.typecheck(compiler, vm, module_name, input));
+            try!(macro_value.typecheck(compiler, vm, module_name, input).map_err(|err| {
+                ImportError {
+                    module: module_name.into(),
+                    source: input.into(),
+                    error: err,
+                }
+            }));
 
-        let lines = source::Lines::new(input);
-        self.0.lock().unwrap().insert(module_name.into(), (lines, expr));
+        self.0.lock().unwrap().insert(module_name.into(), Module::new(input, expr));
         let metadata = Metadata::default();
         // Insert a global to ensure the globals type can be looked up
         try!(vm.global_env()
@@ -74,6 +137,27 @@ impl Importer for CheckImporter {
     }
 }
 
+/// Error returned from `CheckImporter::import` when an imported module fails to typecheck
+#[derive(Debug)]
+struct ImportError {
+    module: String,
+    /// The source of the module
+    source: String,
+    error: GluonError,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Error in imported module `{}`: {}", self.module, self.error)
+    }
+}
+
+impl StdError for ImportError {
+    fn description(&self) -> &str {
+        "Error in imported module"
+    }
+}
+
 struct ServerError<E> {
     message: String,
     data: Option<E>,
@@ -95,7 +179,9 @@ trait LanguageServerNotification: Send + Sync {
 
 struct ServerCommand<T>(T);
 
-impl<T> NotificationCommand for ServerCommand<T>
+struct ServerNotification<T>(T, Output);
+
+impl<T> NotificationCommand for ServerNotification<T>
     where T: LanguageServerNotification,
 {
     fn execute(&self, param: Params) {
@@ -105,10 +191,10 @@ impl<T> NotificationCommand for ServerCommand<T>
                     Ok(value) => {
                         self.0.execute(value);
                     }
-                    Err(_) => log_message(format!("Invalid parameters: {:?}", map)),
+                    Err(_) => log_message(&self.1, format!("Invalid parameters: {:?}", map)),
                 }
             }
-            _ => log_message(format!("Invalid parameters: {:?}", param)),
+            _ => log_message(&self.1, format!("Invalid parameters: {:?}", param)),
         }
     }
 }
@@ -147,31 +233,81 @@ impl<T> MethodCommand for ServerCommand<T>
     }
 }
 
-struct Initialize(RootedThread);
+/// The last field is set if the client can register the file watcher, see `register_file_watcher`
+struct Initialize(Workspaces, SharedEncoding, CheckWorker, Arc<AtomicBool>);
 impl LanguageServerCommand for Initialize {
-    type Param = InitializeParams;
-    type Output = InitializeResult;
+    // `InitializeParams` does not have the workspace folder fields yet
+    type Param = Value;
+    type Output = Value;
     type Error = InitializeError;
-    fn execute(&self,
-               change: InitializeParams)
-               -> Result<InitializeResult, ServerError<InitializeError>> {
-        let import = self.0.get_macros().get("import").expect("Import macro");
-        let import = import.downcast_ref::<Import<CheckImporter>>()
-            .expect("Check importer");
-        if let Some(ref path) = change.root_path {
-            import.add_path(path);
-        }
-        Ok(InitializeResult {
+    fn execute(&self, params: Value) -> Result<Value, ServerError<InitializeError>> {
+        let change: InitializeParams = try!(from_value(params.clone()).map_err(|err| {
+            ServerError {
+                message: format!("Invalid initialize parameters: {}", err),
+                data: Some(InitializeError { retry: false }),
+            }
+        }));
+        // `workspaceFolders` replaces `rootPath` for clients which support it
+        let mut folders = workspace_folders(params.find("workspaceFolders"));
+        if folders.is_empty() {
+            folders.extend(change.root_path
+                .and_then(|path| url::Url::from_directory_path(path).ok())
+                .map(|url| url.to_string()));
+        }
+        for uri in folders {
+            if let Some((thread, path)) = self.0.add_folder(&uri) {
+                self.2.submit_index(&uri, thread, path);
+            }
+        }
+        let encodings: Vec<&str> = params.find_path(&["capabilities",
+                                                      "general",
+                                                      "positionEncodings"])
+            .and_then(|encodings| encodings.as_array())
+            .map(|encodings| encodings.iter().filter_map(|encoding| encoding.as_str()).collect())
+            .unwrap_or_else(Vec::new);
+        let encoding = PositionEncoding::negotiate(encodings);
+        self.1.set(encoding);
+        let watch_files = change.capabilities
+            .find_path(&["workspace", "didChangeWatchedFiles", "dynamicRegistration"])
+            .and_then(|registration| registration.as_bool())
+            .unwrap_or(false);
+        self.3.store(watch_files, atomic::Ordering::SeqCst);
+
+        let mut result = to_value(&InitializeResult {
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncKind::Full),
+                text_document_sync: Some(TextDocumentSyncKind::Incremental),
                 completion_provider: Some(CompletionOptions {
                     resolve_provider: Some(true),
                     trigger_characters: vec![".".into()],
                 }),
                 hover_provider: Some(true),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec![" ".into(), "(".into()]),
+                }),
+                definition_provider: Some(true),
+                references_provider: Some(true),
+                document_symbol_provider: Some(true),
+                document_formatting_provider: Some(true),
+                document_range_formatting_provider: Some(true),
+                workspace_symbol_provider: Some(true),
                 ..ServerCapabilities::default()
             },
-        })
+        });
+        let capabilities = result.as_object_mut()
+            .and_then(|result| result.get_mut("capabilities"))
+            .and_then(|capabilities| capabilities.as_object_mut());
+        if let Some(capabilities) = capabilities {
+            // `RenameOptions` does not exist yet
+            let rename = r#"{ "prepareProvider": true }"#;
+            capabilities.insert("renameProvider".into(), serde_json::from_str(rename).unwrap());
+            let workspace = r#"{
+                "workspaceFolders": { "supported": true, "changeNotifications": true }
+            }"#;
+            capabilities.insert("workspace".into(), serde_json::from_str(workspace).unwrap());
+            capabilities.insert("positionEncoding".into(),
+                                Value::String(encoding.name().into()));
+        }
+        Ok(result)
     }
 
     fn invalid_params(&self) -> Option<Self::Error> {
@@ -179,7 +315,7 @@ impl LanguageServerCommand for Initialize {
     }
 }
 
-struct Completion(RootedThread);
+struct Completion(Workspaces, SharedSettings, Cancellation, SharedEncoding);
 impl LanguageServerCommand for Completion {
     type Param = TextDocumentPositionParams;
     type Output = Vec<CompletionItem>;
@@ -187,39 +323,35 @@ impl LanguageServerCommand for Completion {
     fn execute(&self,
                change: TextDocumentPositionParams)
                -> Result<Vec<CompletionItem>, ServerError<()>> {
-        let thread = &self.0;
+        let thread = &self.0.thread(&change.text_document.uri);
+        let encoding = self.3.get();
         let module = strip_file_prefix(thread, &change.text_document.uri);
         let import = thread.get_macros().get("import").expect("Import macro");
         let import = import.downcast_ref::<Import<CheckImporter>>().expect("Check importer");
         let importer = import.importer.0.lock().unwrap();
-        let &(ref line_map, ref expr) = try!(importer.get(&module).ok_or_else(|| {
+        let module = try!(importer.get(&module).ok_or_else(|| {
             ServerError {
                 message: format!("Module `{}` is not defined", module),
                 data: None,
             }
         }));
-
-        let line_pos = try!(line_map.line(Line::from(change.position.line as usize))
-            .ok_or_else(|| {
-                ServerError {
-                    message: format!("Position ({}, {}) is out of range",
-                                     change.position.line,
-                                     change.position.character),
-                    data: None,
-                }
-            }));
-        let byte_pos = line_pos + BytePos::from(change.position.character as usize);
-        let suggestions = completion::suggest(&*thread.get_env(), expr, byte_pos);
+        let byte_pos = try!(position_to_byte_pos(module, &change.position, encoding));
+        if !self.1.get().features.completion {
+            return Ok(vec![]);
+        }
+        let token = self.2.current();
+        if token.is_cancelled() {
+            return Err(request_cancelled());
+        }
+        let suggestions = completion::suggest(&*thread.get_env(), &module.expr, byte_pos);
+        if token.is_cancelled() {
+            return Err(request_cancelled());
+        }
 
         let mut items: Vec<_> = suggestions.into_iter()
             .map(|ident| {
-                // Remove the `:Line x, Row y suffix`
-                let name: &str = ident.name.as_ref();
-                let label = String::from(name.split(':')
-                    .next()
-                    .unwrap_or(ident.name.as_ref()));
                 CompletionItem {
-                    label: label,
+                    label: query::name(&ident.name).into(),
                     detail: Some(format!("{}", ident.typ)),
                     kind: Some(CompletionItemKind::Variable),
                     ..CompletionItem::default()
@@ -237,36 +369,33 @@ impl LanguageServerCommand for Completion {
     }
 }
 
-struct HoverCommand(RootedThread);
+struct HoverCommand(Workspaces, SharedSettings, SharedEncoding);
 impl LanguageServerCommand for HoverCommand {
     type Param = TextDocumentPositionParams;
     type Output = Hover;
     type Error = ();
     fn execute(&self, change: TextDocumentPositionParams) -> Result<Hover, ServerError<()>> {
-        let thread = &self.0;
+        if !self.1.get().features.hover {
+            return Ok(Hover {
+                contents: vec![],
+                range: None,
+            });
+        }
+        let thread = &self.0.thread(&change.text_document.uri);
+        let encoding = self.2.get();
         let module = strip_file_prefix(thread, &change.text_document.uri);
         let import = thread.get_macros().get("import").expect("Import macro");
         let import = import.downcast_ref::<Import<CheckImporter>>().expect("Check importer");
         let importer = import.importer.0.lock().unwrap();
-        let &(ref line_map, ref expr) = try!(importer.get(&module).ok_or_else(|| {
+        let module = try!(importer.get(&module).ok_or_else(|| {
             ServerError {
                 message: format!("Module `{}` is not defined", module),
                 data: None,
             }
         }));
-
-        let line_pos = try!(line_map.line(Line::from(change.position.line as usize))
-            .ok_or_else(|| {
-                ServerError {
-                    message: format!("Position ({}, {}) is out of range",
-                                     change.position.line,
-                                     change.position.character),
-                    data: None,
-                }
-            }));
-        let byte_pos = line_pos + BytePos::from(change.position.character as usize);
-        completion::find(&*thread.get_env(), expr, byte_pos)
-            .map(|typ| {
+        let byte_pos = try!(position_to_byte_pos(module, &change.position, encoding));
+        let typ = completion::find(&*thread.get_env(), &module.expr, byte_pos);
+        typ.map(|typ| {
                 Hover {
                     contents: vec![MarkedString::String(format!("{}", typ))],
                     range: None,
@@ -287,38 +416,1000 @@ impl LanguageServerCommand for HoverCommand {
     }
 }
 
-fn location_to_position(loc: &pos::Location) -> Position {
-    Position {
-        line: loc.line.to_usize() as u64 + 1,
-        character: loc.column.to_usize() as u64,
+struct SignatureHelpCommand(Workspaces, SharedSettings, SharedEncoding);
+impl LanguageServerCommand for SignatureHelpCommand {
+    type Param = TextDocumentPositionParams;
+    type Output = SignatureHelp;
+    type Error = ();
+    fn execute(&self,
+               change: TextDocumentPositionParams)
+               -> Result<SignatureHelp, ServerError<()>> {
+        let thread = &self.0.thread(&change.text_document.uri);
+        let encoding = self.2.get();
+        let module = strip_file_prefix(thread, &change.text_document.uri);
+        let import = thread.get_macros().get("import").expect("Import macro");
+        let import = import.downcast_ref::<Import<CheckImporter>>().expect("Check importer");
+        let importer = import.importer.0.lock().unwrap();
+        let module = try!(importer.get(&module).ok_or_else(|| {
+            ServerError {
+                message: format!("Module `{}` is not defined", module),
+                data: None,
+            }
+        }));
+        let byte_pos = try!(position_to_byte_pos(module, &change.position, encoding));
+
+        let mut help = SignatureHelp {
+            signatures: vec![],
+            active_signature: None,
+            active_parameter: None,
+        };
+        if !self.1.get().features.signature_help {
+            return Ok(help);
+        }
+        let (func, active_parameter) =
+            match query::call_at(&module.source, &module.expr, byte_pos) {
+                Some(call) => call,
+                None => return Ok(help),
+            };
+        let typ = match completion::find(&*thread.get_env(), &module.expr, func.span.end) {
+            Ok(typ) => typ,
+            Err(()) => return Ok(help),
+        };
+
+        let mut parameters = Vec::new();
+        {
+            let mut typ = &typ;
+            while let Some((arg, ret)) = typ.as_function() {
+                parameters.push(ParameterInformation {
+                    label: format!("{}", arg),
+                    documentation: None,
+                });
+                typ = ret;
+            }
+        }
+        if parameters.is_empty() {
+            return Ok(help);
+        }
+
+        let name = match func.value {
+            Expr::Ident(ref id) => query::name(&id.name),
+            Expr::Projection(_, ref field, _) => query::name(field),
+            _ => "",
+        };
+        help.active_parameter = Some(cmp::min(active_parameter, parameters.len() - 1) as u64);
+        help.active_signature = Some(0);
+        help.signatures.push(SignatureInformation {
+            label: format!("{} : {}", name, typ),
+            documentation: None,
+            parameters: Some(parameters),
+        });
+        Ok(help)
+    }
+
+    fn invalid_params(&self) -> Option<Self::Error> {
+        None
+    }
+}
+
+struct Formatting(Workspaces, DocumentStore, SharedSettings, SharedEncoding);
+impl LanguageServerCommand for Formatting {
+    type Param = DocumentFormattingParams;
+    type Output = Vec<TextEdit>;
+    type Error = ();
+    fn execute(&self,
+               change: DocumentFormattingParams)
+               -> Result<Vec<TextEdit>, ServerError<()>> {
+        if !self.2.get().features.formatting {
+            return Ok(vec![]);
+        }
+        format_document(&self.0.thread(&change.text_document.uri),
+                        &self.1,
+                        &change.text_document.uri,
+                        &change.options,
+                        None,
+                        self.3.get())
+    }
+
+    fn invalid_params(&self) -> Option<Self::Error> {
+        None
+    }
+}
+
+struct RangeFormatting(Workspaces, DocumentStore, SharedSettings, SharedEncoding);
+impl LanguageServerCommand for RangeFormatting {
+    type Param = DocumentRangeFormattingParams;
+    type Output = Vec<TextEdit>;
+    type Error = ();
+    fn execute(&self,
+               change: DocumentRangeFormattingParams)
+               -> Result<Vec<TextEdit>, ServerError<()>> {
+        if !self.2.get().features.formatting {
+            return Ok(vec![]);
+        }
+        format_document(&self.0.thread(&change.text_document.uri),
+                        &self.1,
+                        &change.text_document.uri,
+                        &change.options,
+                        Some(&change.range),
+                        self.3.get())
+    }
+
+    fn invalid_params(&self) -> Option<Self::Error> {
+        None
+    }
+}
+
+fn format_document(thread: &Thread,
+                   documents: &DocumentStore,
+                   uri: &str,
+                   options: &FormattingOptions,
+                   range: Option<&Range>,
+                   encoding: PositionEncoding)
+                   -> Result<Vec<TextEdit>, ServerError<()>> {
+    let text = try!(documents.contents(uri).map_err(|message| {
+        ServerError {
+            message: message,
+            data: None,
+        }
+    }));
+    let name = filename_to_module(&strip_file_prefix(thread, uri));
+    let mut compiler = Compiler::new();
+    let expr = try!(compiler.parse_partial_expr(&name, &text).map_err(|(_, err)| {
+        ServerError {
+            message: format!("Unable to format `{}` as it does not parse: {}",
+                             uri,
+                             GluonError::from(err)),
+            data: None,
+        }
+    }));
+    let formatted = format::pretty(&text, &expr, options);
+    // Refuse to format rather than risk changing the meaning of the program if the output does not
+    // parse back into the same expression
+    let unchanged = compiler.parse_partial_expr(&name, &formatted)
+        .map(|formatted_expr| formatted_expr == expr)
+        .unwrap_or(false);
+    if !unchanged {
+        return Err(ServerError {
+            message: format!("Unable to format `{}`", uri),
+            data: None,
+        });
+    }
+    Ok(format::edits(&text, &formatted, range, encoding))
+}
+
+struct Definition(Workspaces, SharedEncoding);
+impl LanguageServerCommand for Definition {
+    type Param = TextDocumentPositionParams;
+    type Output = Location;
+    type Error = ();
+    fn execute(&self, change: TextDocumentPositionParams) -> Result<Location, ServerError<()>> {
+        let thread = &self.0.thread(&change.text_document.uri);
+        let encoding = self.1.get();
+        let module_name = strip_file_prefix(thread, &change.text_document.uri);
+        let import = thread.get_macros().get("import").expect("Import macro");
+        let import = import.downcast_ref::<Import<CheckImporter>>().expect("Check importer");
+        let importer = import.importer.0.lock().unwrap();
+        let module = try!(importer.get(&module_name).ok_or_else(|| {
+            ServerError {
+                message: format!("Module `{}` is not defined", module_name),
+                data: None,
+            }
+        }));
+        let byte_pos = try!(position_to_byte_pos(module, &change.position, encoding));
+
+        let not_found = || {
+            ServerError {
+                message: format!("Definition not found at: Line {}, Column {}",
+                                 change.position.line,
+                                 change.position.character),
+                data: None,
+            }
+        };
+
+        let occurrences = query::occurrences(&module.source, &module.expr);
+        let occurrence = try!(query::occurrence_at(&occurrences, byte_pos).ok_or_else(&not_found));
+
+        // Names which are bound by destructuring or projecting an imported module are defined in
+        // that module so prefer jumping to the definition there
+        let binding = query::binding(&occurrences, occurrence.symbol).unwrap_or(occurrence);
+        if let Some(origin) = binding.origin {
+            let record: &str = origin.record.as_ref();
+            let imported = loaded_module(&importer, record)
+                .and_then(|imported| module_uri(thread, record).map(|uri| (uri, imported)));
+            if let Some((uri, imported)) = imported {
+                let imported_occurrences = query::occurrences(&imported.source, &imported.expr);
+                let field = query::name(origin.field);
+                if let Some(span) = query::exported_binding(&imported.expr,
+                                                            &imported_occurrences,
+                                                            field) {
+                    return Ok(Location {
+                        uri: uri,
+                        range: imported.lines.range(&imported.source, span, encoding),
+                    });
+                }
+            }
+        }
+        if binding.kind == query::OccurrenceKind::Binding {
+            return Ok(Location {
+                uri: change.text_document.uri.clone(),
+                range: module.lines.range(&module.source, binding.span, encoding),
+            });
+        }
+
+        // Identifiers referring directly to an imported module points at the start of that module
+        let name: &str = occurrence.symbol.as_ref();
+        if occurrence.kind == query::OccurrenceKind::Reference &&
+           loaded_module(&importer, name).is_some() {
+            if let Some(uri) = module_uri(thread, name) {
+                return Ok(Location {
+                    uri: uri,
+                    range: Range::default(),
+                });
+            }
+        }
+        Err(not_found())
+    }
+
+    fn invalid_params(&self) -> Option<Self::Error> {
+        None
+    }
+}
+
+struct References(Workspaces, Cancellation, SharedEncoding);
+impl LanguageServerCommand for References {
+    type Param = ReferenceParams;
+    type Output = Vec<Location>;
+    type Error = ();
+    fn execute(&self, change: ReferenceParams) -> Result<Vec<Location>, ServerError<()>> {
+        let thread = &self.0.thread(&change.text_document.uri);
+        let encoding = self.2.get();
+        let module_name = strip_file_prefix(thread, &change.text_document.uri);
+        let import = thread.get_macros().get("import").expect("Import macro");
+        let import = import.downcast_ref::<Import<CheckImporter>>().expect("Check importer");
+        let importer = import.importer.0.lock().unwrap();
+        let module = try!(importer.get(&module_name).ok_or_else(|| {
+            ServerError {
+                message: format!("Module `{}` is not defined", module_name),
+                data: None,
+            }
+        }));
+        let byte_pos = try!(position_to_byte_pos(module, &change.position, encoding));
+
+        let occurrences = query::occurrences(&module.source, &module.expr);
+        let symbol = try!(query::occurrence_at(&occurrences, byte_pos)
+            .map(|occurrence| occurrence.symbol)
+            .ok_or_else(|| {
+                ServerError {
+                    message: format!("Symbol not found at: Line {}, Column {}",
+                                     change.position.line,
+                                     change.position.character),
+                    data: None,
+                }
+            }));
+
+        let token = self.1.current();
+        let include_declaration = change.context.include_declaration;
+        let mut locations = Vec::new();
+        let uses = symbol_uses(&importer, &token, &module_name, &occurrences, symbol);
+        for (name, module, symbols) in uses {
+            if token.is_cancelled() {
+                return Err(request_cancelled());
+            }
+            let uri = if name == module_name {
+                change.text_document.uri.clone()
+            } else {
+                // Modules without a file have no location to report
+                match module_uri(thread, &name) {
+                    Some(uri) => uri,
+                    None => continue,
+                }
+            };
+            let occurrences = query::occurrences(&module.source, &module.expr);
+            locations.extend(occurrences.iter()
+                .filter(|occurrence| symbols.contains(&occurrence.symbol))
+                .filter(|occurrence| {
+                    include_declaration || occurrence.kind != query::OccurrenceKind::Binding
+                })
+                .map(|occurrence| {
+                    Location {
+                        uri: uri.clone(),
+                        range: module.lines.range(&module.source, occurrence.span, encoding),
+                    }
+                }));
+        }
+        Ok(locations)
+    }
+
+    fn invalid_params(&self) -> Option<Self::Error> {
+        None
+    }
+}
+
+struct PrepareRename(Workspaces, SharedEncoding);
+impl LanguageServerCommand for PrepareRename {
+    type Param = TextDocumentPositionParams;
+    type Output = Range;
+    type Error = ();
+    fn execute(&self, change: TextDocumentPositionParams) -> Result<Range, ServerError<()>> {
+        let thread = &self.0.thread(&change.text_document.uri);
+        let encoding = self.1.get();
+        let module_name = strip_file_prefix(thread, &change.text_document.uri);
+        let import = thread.get_macros().get("import").expect("Import macro");
+        let import = import.downcast_ref::<Import<CheckImporter>>().expect("Check importer");
+        let importer = import.importer.0.lock().unwrap();
+        let module = try!(importer.get(&module_name).ok_or_else(|| {
+            ServerError {
+                message: format!("Module `{}` is not defined", module_name),
+                data: None,
+            }
+        }));
+        let byte_pos = try!(position_to_byte_pos(module, &change.position, encoding));
+
+        let occurrences = query::occurrences(&module.source, &module.expr);
+        let occurrence = try!(renameable_occurrence(&occurrences, byte_pos));
+        Ok(module.lines.range(&module.source, occurrence.span, encoding))
+    }
+
+    fn invalid_params(&self) -> Option<Self::Error> {
+        None
+    }
+}
+
+struct Rename(Workspaces, Cancellation, SharedEncoding);
+impl LanguageServerCommand for Rename {
+    type Param = RenameParams;
+    type Output = WorkspaceEdit;
+    type Error = ();
+    fn execute(&self, change: RenameParams) -> Result<WorkspaceEdit, ServerError<()>> {
+        let thread = &self.0.thread(&change.text_document.uri);
+        let encoding = self.2.get();
+        let module_name = strip_file_prefix(thread, &change.text_document.uri);
+        let import = thread.get_macros().get("import").expect("Import macro");
+        let import = import.downcast_ref::<Import<CheckImporter>>().expect("Check importer");
+        let importer = import.importer.0.lock().unwrap();
+        let module = try!(importer.get(&module_name).ok_or_else(|| {
+            ServerError {
+                message: format!("Module `{}` is not defined", module_name),
+                data: None,
+            }
+        }));
+        let byte_pos = try!(position_to_byte_pos(module, &change.position, encoding));
+
+        let occurrences = query::occurrences(&module.source, &module.expr);
+        let occurrence = try!(renameable_occurrence(&occurrences, byte_pos));
+        let old_name = query::name(occurrence.symbol);
+        let new_name = &change.new_name[..];
+        if !is_valid_rename(old_name, new_name) {
+            return Err(ServerError {
+                message: format!("`{}` is not a valid name for `{}`", new_name, old_name),
+                data: None,
+            });
+        }
+
+        // Fields are only renamed in records of the same type as the record at the position
+        let record_fields = if occurrence.kind == query::OccurrenceKind::Field {
+            Some(try!(occurrence.record_type.and_then(query::record_fields).ok_or_else(|| {
+                ServerError {
+                    message: format!("Unable to rename the field `{}` as the type of its record \
+                                      is not known",
+                                     old_name),
+                    data: None,
+                }
+            })))
+        } else {
+            None
+        };
+        let record_fields = record_fields.as_ref().map(|fields| &fields[..]);
+
+        let token = self.1.current();
+        let mut changes = HashMap::new();
+        let uses = symbol_uses(&importer, &token, &module_name, &occurrences, occurrence.symbol);
+        for (name, module, symbols) in uses {
+            if token.is_cancelled() {
+                return Err(request_cancelled());
+            }
+            let occurrences = query::occurrences(&module.source, &module.expr);
+            let renamed = try!(renamed_occurrences(&occurrences, &symbols, record_fields));
+            if let Some(conflict) = rename_conflict(&occurrences, &renamed, new_name) {
+                let position =
+                    module.lines.position(&module.source, conflict.span.start.to_usize(), encoding);
+                return Err(ServerError {
+                    message: format!("Renaming `{}` to `{}` would collide with the existing \
+                                      `{}` in `{}` at Line {}, Column {}",
+                                     old_name,
+                                     new_name,
+                                     new_name,
+                                     name,
+                                     position.line,
+                                     position.character),
+                    data: None,
+                });
+            }
+            let uri = if name == module_name {
+                change.text_document.uri.clone()
+            } else {
+                try!(module_uri(thread, &name).ok_or_else(|| {
+                    ServerError {
+                        message: format!("Unable to rename `{}` as it is used in `{}` which is \
+                                          not loaded from a file",
+                                         old_name,
+                                         name),
+                        data: None,
+                    }
+                }))
+            };
+            let edits = renamed.iter()
+                .map(|occurrence| {
+                    TextEdit {
+                        range: module.lines.range(&module.source, occurrence.span, encoding),
+                        new_text: new_name.to_string(),
+                    }
+                })
+                .collect();
+            changes.insert(uri, edits);
+        }
+        Ok(WorkspaceEdit { changes: changes })
+    }
+
+    fn invalid_params(&self) -> Option<Self::Error> {
+        None
+    }
+}
+
+struct DocumentSymbols(Workspaces, SharedEncoding);
+impl LanguageServerCommand for DocumentSymbols {
+    type Param = DocumentSymbolParams;
+    type Output = Vec<Value>;
+    type Error = ();
+    fn execute(&self, change: DocumentSymbolParams) -> Result<Vec<Value>, ServerError<()>> {
+        let thread = &self.0.thread(&change.text_document.uri);
+        let encoding = self.1.get();
+        let module_name = strip_file_prefix(thread, &change.text_document.uri);
+        let import = thread.get_macros().get("import").expect("Import macro");
+        let import = import.downcast_ref::<Import<CheckImporter>>().expect("Check importer");
+        let importer = import.importer.0.lock().unwrap();
+        let module = try!(importer.get(&module_name).ok_or_else(|| {
+            ServerError {
+                message: format!("Module `{}` is not defined", module_name),
+                data: None,
+            }
+        }));
+
+        Ok(symbols::document_symbols(&module.source, &module.expr)
+            .iter()
+            .map(|symbol| symbol.to_value(&module.source, &module.lines, encoding))
+            .collect())
+    }
+
+    fn invalid_params(&self) -> Option<Self::Error> {
+        None
+    }
+}
+
+/// Returns the uris of the folders in a list of `WorkspaceFolder`s
+fn workspace_folders(folders: Option<&Value>) -> Vec<String> {
+    folders.and_then(|folders| folders.as_array())
+        .map(|folders| {
+            folders.iter()
+                .filter_map(|folder| folder.find("uri").and_then(|uri| uri.as_str()))
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_else(Vec::new)
+}
+
+struct DidChangeWorkspaceFolders(Workspaces, DocumentStore, CheckWorker);
+impl LanguageServerNotification for DidChangeWorkspaceFolders {
+    type Param = Value;
+
+    fn execute(&self, change: Value) {
+        let event = change.find("event");
+        for uri in workspace_folders(event.and_then(|event| event.find("removed"))) {
+            self.0.remove_folder(&uri);
+            // Stop indexing the folder if it has not finished yet
+            self.2.cancel(&uri);
+        }
+        for uri in workspace_folders(event.and_then(|event| event.find("added"))) {
+            if let Some((thread, path)) = self.0.add_folder(&uri) {
+                self.2.submit_index(&uri, thread, path);
+            }
+        }
+        // Documents may have moved to the vm of another folder
+        for (uri, document) in self.1.documents() {
+            if !self.1.is_foreign(&uri) {
+                self.2.submit(&uri, document.text);
+            }
+        }
+    }
+}
+
+struct WorkspaceSymbols(Workspaces, DocumentStore, Cancellation, SharedEncoding);
+impl LanguageServerCommand for WorkspaceSymbols {
+    type Param = WorkspaceSymbolParams;
+    type Output = Vec<SymbolInformation>;
+    type Error = ();
+    fn execute(&self,
+               change: WorkspaceSymbolParams)
+               -> Result<Vec<SymbolInformation>, ServerError<()>> {
+        let token = self.2.current();
+        let encoding = self.3.get();
+        let mut matches = Vec::new();
+        // Modules such as the standard library are loaded into every vm but are only reported once
+        let mut seen = HashSet::new();
+        for thread in self.0.threads() {
+            let import = thread.get_macros().get("import").expect("Import macro");
+            let import = import.downcast_ref::<Import<CheckImporter>>().expect("Check importer");
+            let importer = import.importer.0.lock().unwrap();
+
+            // A file which is open and also imported is stored under both its filename and its
+            // module name, prefer the open document as it may have been edited
+            let mut modules = HashMap::new();
+            for (module_name, module) in importer.iter() {
+                // Documents which are open without a file are stored under their uri
+                let uri = match module_uri(&thread, module_name) {
+                    Some(uri) => uri,
+                    None if self.1.get(module_name).is_some() => module_name.clone(),
+                    None => continue,
+                };
+                if seen.contains(&uri) {
+                    continue;
+                }
+                if module_name.ends_with(".glu") || !modules.contains_key(&uri) {
+                    modules.insert(uri, module);
+                }
+            }
+
+            for (uri, module) in modules {
+                if token.is_cancelled() {
+                    return Err(request_cancelled());
+                }
+                let document_symbols = symbols::document_symbols(&module.source, &module.expr);
+                for (symbol, container) in symbols::flatten(&document_symbols) {
+                    if let Some(score) = symbols::fuzzy_match(&change.query, &symbol.name) {
+                        let information = SymbolInformation {
+                            name: symbol.name.clone(),
+                            kind: symbol.kind,
+                            location: Location {
+                                uri: uri.clone(),
+                                range: module.lines.range(&module.source,
+                                                          symbol.selection_span,
+                                                          encoding),
+                            },
+                            container_name: container.map(|container| container.name.clone()),
+                        };
+                        matches.push((score, information));
+                    }
+                }
+                seen.insert(uri);
+            }
+        }
+        // Best matches first, ties are sorted by name
+        matches.sort_by(|l, r| (r.0, &l.1.name).cmp(&(l.0, &r.1.name)));
+        Ok(matches.into_iter().map(|(_, information)| information).collect())
+    }
+
+    fn invalid_params(&self) -> Option<Self::Error> {
+        None
+    }
+}
+
+/// Typechecks every gluon file under `root` so that they are available to workspace wide queries.
+/// Stops early if `token` is cancelled.
+fn index_workspace(thread: &Thread, token: &CancellationToken, root: &Path) {
+    let mut files = Vec::new();
+    find_gluon_files(root, &mut files);
+    for file in files {
+        let mut input = String::new();
+        let read = fs::File::open(&file).and_then(|mut file| file.read_to_string(&mut input));
+        let uri = url::Url::from_file_path(&file).ok();
+        match (read, uri) {
+            (Ok(_), Some(uri)) => {
+                match typecheck(thread, token, uri.as_str(), &input) {
+                    Some(Ok(())) => (),
+                    Some(Err(err)) => debug!("Error indexing `{}`: {}", file.display(), err),
+                    None => return,
+                }
+            }
+            _ => debug!("Unable to index `{}`", file.display()),
+        }
+    }
+}
+
+fn find_gluon_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let hidden = path.file_name()
+            .and_then(|name| name.to_str())
+            .map_or(false, |name| name.starts_with('.') || name == "target");
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            find_gluon_files(&path, files);
+        } else if path.extension().map_or(false, |ext| ext == "glu") {
+            files.push(path);
+        }
+    }
+}
+
+/// Returns the occurrence at `pos` if it is a name which is defined in the module
+fn renameable_occurrence<'a, 'b>(occurrences: &'b [query::Occurrence<'a>],
+                                 pos: BytePos)
+                                 -> Result<&'b query::Occurrence<'a>, ServerError<()>> {
+    let occurrence = try!(query::occurrence_at(occurrences, pos).ok_or_else(|| {
+        ServerError {
+            message: "No symbol to rename at the position".into(),
+            data: None,
+        }
+    }));
+    let defined_locally = occurrence.kind == query::OccurrenceKind::Field ||
+                          query::binding(occurrences, occurrence.symbol).is_some();
+    if defined_locally {
+        Ok(occurrence)
+    } else {
+        Err(ServerError {
+            message: format!("`{}` is not defined in this module and can't be renamed",
+                             query::name(occurrence.symbol)),
+            data: None,
+        })
+    }
+}
+
+/// Checks that `new_name` is an identifier of the same kind as `old_name` as types and
+/// constructors must start with an uppercase letter while variables must not
+fn is_valid_rename(old_name: &str, new_name: &str) -> bool {
+    let mut chars = new_name.chars();
+    match chars.next() {
+        Some(first) => {
+            let same_case = old_name.chars().next().map_or(false, |c| c.is_uppercase()) ==
+                            first.is_uppercase();
+            same_case && (first.is_alphabetic() || first == '_') &&
+            chars.all(|c| c.is_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+/// Returns the occurrences in `occurrences` which are renamed along with `symbols`. When renaming
+/// the field of a record only the fields of records with the same fields (`record_fields`) are
+/// renamed, fields whose record type is unknown make the rename fail as they may or may not belong
+/// to the same record.
+fn renamed_occurrences<'a, 'b>(occurrences: &'b [query::Occurrence<'a>],
+                               symbols: &[&Symbol],
+                               record_fields: Option<&[&str]>)
+                               -> Result<Vec<&'b query::Occurrence<'a>>, ServerError<()>> {
+    let mut renamed = Vec::new();
+    for occurrence in occurrences.iter().filter(|occurrence| symbols.contains(&occurrence.symbol)) {
+        let record_fields = match record_fields {
+            Some(record_fields) => record_fields,
+            None => {
+                renamed.push(occurrence);
+                continue;
+            }
+        };
+        if occurrence.kind != query::OccurrenceKind::Field {
+            continue;
+        }
+        match occurrence.record_type.and_then(query::record_fields) {
+            Some(ref fields) if &fields[..] == record_fields => renamed.push(occurrence),
+            Some(_) => (),
+            None => {
+                return Err(ServerError {
+                    message: format!("Unable to rename the field `{}` as the type of a record \
+                                      it is used with is not known",
+                                     query::name(occurrence.symbol)),
+                    data: None,
+                })
+            }
+        }
+    }
+    Ok(renamed)
+}
+
+/// Finds an occurrence named `new_name` which would shadow or be shadowed by the `renamed`
+/// occurrences
+fn rename_conflict<'a, 'b>(occurrences: &'b [query::Occurrence<'a>],
+                           renamed: &[&'b query::Occurrence<'a>],
+                           new_name: &str)
+                           -> Option<&'b query::Occurrence<'a>> {
+    use query::OccurrenceKind;
+
+    let contains = |scope: &Span<BytePos>, span: &Span<BytePos>| {
+        scope.start <= span.start && span.end <= scope.end
+    };
+    occurrences.iter()
+        .filter(|other| query::name(other.symbol) == new_name)
+        .filter(|other| !renamed.iter().any(|renamed| renamed.span == other.span))
+        .find(|other| {
+            // Types and values do not share a namespace
+            renamed.iter().filter(|renamed| renamed.is_type == other.is_type).any(|renamed| {
+                match (renamed.kind, other.kind) {
+                    // Fields only collide with the fields of the same record
+                    (OccurrenceKind::Field, OccurrenceKind::Field) => {
+                        let other_fields = other.record_type.and_then(query::record_fields);
+                        other_fields.is_none() ||
+                        renamed.record_type.and_then(query::record_fields) == other_fields
+                    }
+                    (OccurrenceKind::Field, _) |
+                    (_, OccurrenceKind::Field) => false,
+                    // A renamed binding would capture any use of the other name within its scope
+                    (OccurrenceKind::Binding, _) => contains(&renamed.scope, &other.span),
+                    // Any use of the renamed name would be captured by the other binding
+                    (_, OccurrenceKind::Binding) => contains(&other.scope, &renamed.span),
+                    (OccurrenceKind::Reference, OccurrenceKind::Reference) => false,
+                }
+            })
+        })
+}
+
+/// Returns every module which uses `symbol` (defined in `module_name`) along with the symbols
+/// each module refers to it with. Stops searching if `token` is cancelled.
+fn symbol_uses<'a>(importer: &'a FnvMap<String, Module>,
+                   token: &CancellationToken,
+                   module_name: &str,
+                   occurrences: &[query::Occurrence<'a>],
+                   symbol: &'a Symbol)
+                   -> Vec<(String, &'a Module, Vec<&'a Symbol>)> {
+    let mut uses = Vec::new();
+    let module = match importer.get(module_name) {
+        Some(module) => module,
+        None => return uses,
+    };
+    uses.push((module_name.to_string(), module, vec![symbol]));
+
+    // Symbols are unique to each module so other modules can only refer to the symbol through
+    // the record the module exports
+    let field = query::name(symbol);
+    let exported = query::binding(occurrences, symbol)
+        .and_then(|binding| {
+            query::exported_binding(&module.expr, occurrences, field)
+                .map(|span| span == binding.span)
+        })
+        .unwrap_or(false);
+    if exported {
+        let exporting_module = filename_to_module(module_name);
+        for (other_name, other) in importer.iter() {
+            if token.is_cancelled() {
+                break;
+            }
+            if *other_name == module_name {
+                continue;
+            }
+            let imported_symbols: Vec<_> = query::occurrences(&other.source, &other.expr)
+                .iter()
+                .filter(|occurrence| {
+                    occurrence.origin.map_or(false, |origin| {
+                        let record: &str = origin.record.as_ref();
+                        record == exporting_module && query::name(origin.field) == field
+                    })
+                })
+                .map(|occurrence| occurrence.symbol)
+                .collect();
+            if !imported_symbols.is_empty() {
+                uses.push((other_name.clone(), other, imported_symbols));
+            }
+        }
     }
+    uses
 }
-fn span_to_range(span: &Span<pos::Location>) -> Range {
-    Range {
-        start: location_to_position(&span.start),
-        end: location_to_position(&span.end),
+
+fn request_cancelled<E>() -> ServerError<E> {
+    ServerError {
+        message: "Request cancelled".into(),
+        data: None,
     }
 }
 
-struct TextDocumentDidOpen(RootedThread);
+fn position_to_byte_pos(module: &Module,
+                        position: &Position,
+                        encoding: PositionEncoding)
+                        -> Result<BytePos, ServerError<()>> {
+    module.lines.offset(&module.source, position, encoding).map(BytePos::from).ok_or_else(|| {
+        ServerError {
+            message: format!("Position ({}, {}) is out of range",
+                             position.line,
+                             position.character),
+            data: None,
+        }
+    })
+}
+
+/// Returns the uri of the file which `module` was loaded from or `None` if it was not loaded from
+/// a file (such as the modules of the standard library which are embedded in gluon)
+fn module_uri(thread: &Thread, module: &str) -> Option<String> {
+    let import = thread.get_macros()
+        .get("import")
+        .expect("Import macro");
+    let import = import.downcast_ref::<Import<CheckImporter>>()
+        .expect("Check importer");
+    let paths = import.paths.read().unwrap();
+
+    // Modules opened in the editor are stored under their path while imported modules are stored
+    // under their module name
+    let filename = if module.ends_with(".glu") {
+        module.to_string()
+    } else {
+        format!("{}.glu", module.replace(".", "/"))
+    };
+    paths.iter()
+        .filter_map(|path| fs::canonicalize(path.join(&filename)).ok())
+        .filter_map(|path| url::Url::from_file_path(path).ok())
+        .map(|url| url.to_string())
+        .next()
+}
+
+/// Returns the module named `module`. Modules loaded through `import` are stored under their
+/// module name while modules which were checked as files (open documents) are stored under their
+/// filename.
+fn loaded_module<'a>(importer: &'a FnvMap<String, Module>, module: &str) -> Option<&'a Module> {
+    importer.get(module).or_else(|| importer.get(&format!("{}.glu", module.replace(".", "/"))))
+}
+
+struct TextDocumentDidOpen(DocumentStore, CheckWorker);
 impl LanguageServerNotification for TextDocumentDidOpen {
     type Param = DidOpenTextDocumentParams;
 
     fn execute(&self, change: DidOpenTextDocumentParams) {
-        run_diagnostics(&self.0,
-                        &change.text_document.uri,
-                        &change.text_document.text);
+        let uri = &change.text_document.uri;
+        self.0.open(uri,
+                    change.text_document.version.unwrap_or(0),
+                    change.text_document.language_id.clone(),
+                    change.text_document.text.clone());
+        if !self.0.is_foreign(uri) {
+            self.1.submit(uri, change.text_document.text);
+        }
     }
 }
 
-struct TextDocumentDidChange(RootedThread);
+struct TextDocumentDidChange(DocumentStore, CheckWorker, Output, SharedEncoding);
 impl LanguageServerNotification for TextDocumentDidChange {
     type Param = DidChangeTextDocumentParams;
 
     fn execute(&self, change: DidChangeTextDocumentParams) {
-        run_diagnostics(&self.0,
-                        &change.text_document.uri,
-                        &change.content_changes[0].text);
+        let uri = &change.text_document.uri;
+        let version = change.text_document.version;
+        match self.0.change(uri, version, &change.content_changes, self.3.get()) {
+            Ok(_) if self.0.is_foreign(uri) => (),
+            Ok(text) => self.1.submit(uri, text),
+            Err(err) => log_message(&self.2, err),
+        }
+    }
+}
+
+struct TextDocumentDidClose(Workspaces, DocumentStore, CheckWorker, Output);
+impl LanguageServerNotification for TextDocumentDidClose {
+    type Param = DidCloseTextDocumentParams;
+
+    fn execute(&self, change: DidCloseTextDocumentParams) {
+        let uri = &change.text_document.uri;
+        let foreign = self.1.is_foreign(uri);
+        if self.1.close(uri).is_none() {
+            log_message(&self.3, format!("Received a close for `{}` which was not open", uri));
+        }
+        self.2.cancel(uri);
+        publish_diagnostics(&self.3, uri, vec![]);
+        if foreign {
+            return;
+        }
+
+        // The module goes back to what is on disk, which may not include the edits made in the
+        // editor, so importers see the same module they would see if it had never been opened
+        match self.1.contents(uri) {
+            Ok(text) => self.2.submit_closed(uri, text),
+            Err(_) => {
+                let thread = &self.0.thread(uri);
+                let module_name = strip_file_prefix(thread, uri);
+                let import = thread.get_macros().get("import").expect("Import macro");
+                let import = import.downcast_ref::<Import<CheckImporter>>()
+                    .expect("Check importer");
+                import.importer.0.lock().unwrap().remove(&module_name);
+            }
+        }
+    }
+}
+
+struct TextDocumentDidSave(DocumentStore, CheckWorker, Output);
+impl LanguageServerNotification for TextDocumentDidSave {
+    type Param = DidSaveTextDocumentParams;
+
+    fn execute(&self, change: DidSaveTextDocumentParams) {
+        let uri = &change.text_document.uri;
+        if self.0.is_foreign(uri) {
+            return;
+        }
+        match self.0.contents(uri) {
+            Ok(text) => self.1.submit(uri, text),
+            Err(err) => log_message(&self.2, err),
+        }
+    }
+}
+
+struct DidChangeConfiguration(Workspaces, DocumentStore, SharedSettings, CheckWorker);
+impl LanguageServerNotification for DidChangeConfiguration {
+    type Param = DidChangeConfigurationParams;
+
+    fn execute(&self, change: DidChangeConfigurationParams) {
+        let settings = Settings::from_value(&change.settings);
+        let previous = self.2.set(settings.clone());
+        if previous == settings {
+            return;
+        }
+
+        if previous.import_paths != settings.import_paths {
+            let paths = settings.import_paths.iter().map(PathBuf::from).collect();
+            for (uri, thread, path) in self.0.set_settings_paths(paths) {
+                self.3.submit_index(&uri, thread, path);
+            }
+        }
+
+        for (uri, document) in self.1.documents() {
+            if !self.1.is_foreign(&uri) {
+                self.3.submit(&uri, document.text);
+            }
+        }
+    }
+}
+
+struct DidChangeWatchedFiles(Workspaces, DocumentStore, CheckWorker, Output);
+impl LanguageServerNotification for DidChangeWatchedFiles {
+    type Param = DidChangeWatchedFilesParams;
+
+    fn execute(&self, change: DidChangeWatchedFilesParams) {
+        for event in change.changes {
+            let uri = &event.uri;
+            // Open documents are kept up to date by the editor
+            if self.1.get(uri).is_some() {
+                continue;
+            }
+            match event.typ {
+                FileChangeType::Created |
+                FileChangeType::Changed => {
+                    match self.1.contents(uri) {
+                        Ok(text) => self.2.submit(uri, text),
+                        Err(err) => log_message(&self.3, err),
+                    }
+                }
+                FileChangeType::Deleted => {
+                    let thread = &self.0.thread(uri);
+                    let filename = strip_file_prefix(thread, uri);
+                    {
+                        let import = thread.get_macros().get("import").expect("Import macro");
+                        let import = import.downcast_ref::<Import<CheckImporter>>()
+                            .expect("Check importer");
+                        let mut importer = import.importer.0.lock().unwrap();
+                        importer.remove(&filename);
+                        importer.remove(&filename_to_module(&filename));
+                    }
+                    self.2.cancel(uri);
+                    publish_diagnostics(&self.3, uri, vec![]);
+                    // The type of the module stays in the global environment (it can't be
+                    // removed) so dependents are checked against its last version
+                    self.2.submit_removed(uri);
+                }
+            }
+        }
+    }
+}
+
+/// Id of the `client/registerCapability` request sent by `register_file_watcher`
+const REGISTER_FILE_WATCHER: u64 = 1;
+
+/// Asks the client to send `workspace/didChangeWatchedFiles` for gluon files
+fn register_file_watcher(output: &Output) {
+    let params = serde_json::from_str::<Value>(r#"{
+            "registrations": [{
+                "id": "gluon-watched-files",
+                "method": "workspace/didChangeWatchedFiles",
+                "registerOptions": { "watchers": [{ "globPattern": "**/*.glu" }] }
+            }]
+        }"#)
+        .unwrap();
+    if let Err(err) = output.request(REGISTER_FILE_WATCHER, "client/registerCapability", &params) {
+        error!("Unable to register the file watcher: {}", err);
     }
 }
 
@@ -352,110 +1443,370 @@ fn strip_file_prefix(thread: &Thread, filename: &str) -> String {
     filename.to_string()
 }
 
-fn typecheck(thread: &Thread, filename: &str, fileinput: &str) -> GluonResult<()> {
+/// Parses, expands and typechecks `fileinput` and stores the resulting expression in the
+/// `CheckImporter`. The errors of every stage are returned together as one error.
+///
+/// Returns `None` without storing anything if `token` is cancelled before the check finishes.
+fn typecheck(thread: &Thread,
+             token: &CancellationToken,
+             filename: &str,
+             fileinput: &str)
+             -> Option<GluonResult<()>> {
     use gluon::compiler_pipeline::*;
 
     let filename = strip_file_prefix(thread, filename);
     let name = filename_to_module(&filename);
     let mut compiler = Compiler::new();
-    // The parser may find parse errors but still produce an expression
-    // For that case still typecheck the expression but return the parse error afterwards
-    let (mut expr, parse_result): (_, GluonResult<()>) =
-        match compiler.parse_partial_expr(&name, fileinput) {
-            Ok(expr) => (expr, Ok(())),
-            Err((None, err)) => return Err(err.into()),
-            Err((Some(expr), err)) => (expr, Err(err.into())),
-        };
-    try!(expr.expand_macro(&mut compiler, thread, &name));
-    let result = match compiler.typecheck_expr(thread, &name, fileinput, &mut expr) {
-        Ok(typ) => {
-            let metadata = Metadata::default();
-            try!(thread.global_env()
-                .set_global(Symbol::from(&filename[..]),
-                            typ,
-                            metadata,
-                            GluonValue::Int(0)));
-            Ok(())
+    let mut errors = Vec::new();
+    // The parser may find parse errors but still produce an expression. That expression is still
+    // expanded and typechecked so that the errors of every stage are reported together.
+    let mut expr = match compiler.parse_partial_expr(&name, fileinput) {
+        Ok(expr) => expr,
+        Err((None, err)) => return Some(Err(err.into())),
+        Err((Some(expr), err)) => {
+            errors.push(err.into());
+            expr
         }
-        Err(err) => Err(err),
     };
+    if token.is_cancelled() {
+        return None;
+    }
     let import = thread.get_macros().get("import").expect("Import macro");
     let import = import.downcast_ref::<Import<CheckImporter>>()
         .expect("Check importer");
+    // Imported modules which are already loaded are not imported again so the imports are
+    // recorded here rather than by the importer
+    import.importer.1.lock().unwrap().set_imports(&name, import_graph::imports(&expr));
+
+    // Every failing import or macro adds an error of its own
+    if let Err(err) = expr.expand_macro(&mut compiler, thread, &name) {
+        flatten_errors(err.into(), &mut errors);
+    }
+    if token.is_cancelled() {
+        return None;
+    }
+    // `Compiler::typecheck_expr` would expand the macros again
+    let result = MacroValue { expr: &mut expr }
+        .typecheck(&mut compiler, thread, &name, fileinput)
+        .and_then(|TypecheckValue { typ, .. }| {
+            // Modules which import this module look up its type by the module name
+            if name != filename {
+                try!(thread.global_env()
+                    .set_global(Symbol::from(&name[..]),
+                                typ.clone(),
+                                Metadata::default(),
+                                GluonValue::Int(0)));
+            }
+            let metadata = Metadata::default();
+            thread.global_env()
+                .set_global(Symbol::from(&filename[..]),
+                            typ,
+                            metadata,
+                            GluonValue::Int(0))
+                .map_err(GluonError::from)
+        });
+    if token.is_cancelled() {
+        return None;
+    }
     let mut importer = import.importer.0.lock().unwrap();
 
-    let lines = source::Lines::new(fileinput);
-    importer.insert(filename.into(), (lines, expr));
-    result.or(parse_result)
+    importer.insert(filename.into(), Module::new(fileinput, expr));
+    if let Err(err) = result {
+        errors.push(err);
+    }
+    if errors.is_empty() {
+        Some(Ok(()))
+    } else {
+        Some(Err(Errors { errors: errors }.into()))
+    }
 }
 
-fn run_diagnostics(thread: &Thread, filename: &str, fileinput: &str) {
-    let diagnostics = match typecheck(thread, filename, fileinput) {
-        Ok(_) => vec![],
+fn run_diagnostics(output: &Output,
+                   thread: &Thread,
+                   settings: &Settings,
+                   encoding: PositionEncoding,
+                   token: &CancellationToken,
+                   filename: &str,
+                   fileinput: &str) {
+    if !settings.features.diagnostics {
+        publish_diagnostics(output, filename, vec![]);
+        return;
+    }
+    let result = match typecheck(thread, token, filename, fileinput) {
+        Some(result) => result,
+        // A newer check will publish the diagnostics instead
+        None => return,
+    };
+    let mut diagnostics = match result {
+        Ok(_) => lint_diagnostics(thread, filename, fileinput, &settings.lints, encoding),
         Err(err) => {
-            match err {
-                GluonError::Typecheck(err) => {
-                    err.errors()
-                        .errors
-                        .into_iter()
-                        .map(|err| {
-                            Diagnostic {
-                                message: format!("{}", err.value),
-                                severity: Some(DiagnosticSeverity::Error),
-                                range: span_to_range(&err.span),
-                                ..Diagnostic::default()
-                            }
-                        })
-                        .collect()
-                }
-                GluonError::Parse(err) => {
-                    err.errors()
-                        .errors
-                        .into_iter()
-                        .map(|err| {
-                            let p = Position {
-                                line: err.span.start.line.to_usize() as u64 - 1,
-                                character: err.span.start.column.to_usize() as u64,
-                            };
-                            Diagnostic {
-                                message: format!("{}", err),
-                                severity: Some(DiagnosticSeverity::Error),
-                                range: Range { start: p, end: p },
-                                ..Diagnostic::default()
-                            }
-                        })
-                        .collect()
-                }
-                err => {
-                    vec![Diagnostic {
-                             message: format!("{}", err),
-                             severity: Some(DiagnosticSeverity::Error),
-                             ..Diagnostic::default()
-                         }]
+            let mut publish = |uri: String, diagnostics| {
+                publish_diagnostics(output, &uri, diagnostics)
+            };
+            module_error_diagnostics(thread,
+                                     settings,
+                                     encoding,
+                                     filename,
+                                     fileinput,
+                                     err,
+                                     &mut publish)
+        }
+    };
+    diagnostics.truncate(settings.max_number_of_problems);
+    publish_diagnostics(output, filename, diagnostics);
+}
+
+/// Converts the error found while checking the module at `uri` (`source`) to diagnostics. The
+/// diagnostics of modules which failed to import are passed to `publish` along with the uri of
+/// their file.
+fn module_error_diagnostics(thread: &Thread,
+                            settings: &Settings,
+                            encoding: PositionEncoding,
+                            uri: &str,
+                            source: &str,
+                            err: GluonError,
+                            publish: &mut FnMut(String, Vec<Report>))
+                            -> Vec<Report> {
+    let mut errors = Vec::new();
+    flatten_errors(err, &mut errors);
+    let mut diagnostics = Vec::new();
+    for err in errors {
+        let err_diagnostics = match err {
+            GluonError::Macro(err) => {
+                match err.downcast::<ImportError>() {
+                    Ok(err) => {
+                        import_error_diagnostics(thread, settings, encoding, source, *err, publish)
+                    }
+                    Err(err) => {
+                        error_diagnostics(uri, source, None, GluonError::Macro(err), encoding)
+                    }
                 }
             }
+            err => {
+                // Modules are stored even if they fail to typecheck so their expression can be
+                // used to find the locations related to the errors
+                let import = thread.get_macros().get("import").expect("Import macro");
+                let import = import.downcast_ref::<Import<CheckImporter>>()
+                    .expect("Check importer");
+                let importer = import.importer.0.lock().unwrap();
+                let module = importer.get(&strip_file_prefix(thread, uri));
+                error_diagnostics(uri, source, module.map(|module| &module.expr), err, encoding)
+            }
+        };
+        diagnostics.extend(err_diagnostics);
+    }
+    diagnostics
+}
+
+/// Pushes `err` to `errors`, replacing every `GluonError::Multiple` with the errors it contains
+fn flatten_errors(err: GluonError, errors: &mut Vec<GluonError>) {
+    match err {
+        GluonError::Multiple(multiple) => {
+            for err in multiple.errors {
+                flatten_errors(err, errors);
+            }
+        }
+        err => errors.push(err),
+    }
+}
+
+/// Passes the errors of the module which failed to import to `publish` and returns a diagnostic
+/// for the `import` of it in the importing module (`source`)
+fn import_error_diagnostics(thread: &Thread,
+                            settings: &Settings,
+                            encoding: PositionEncoding,
+                            source: &str,
+                            err: ImportError,
+                            publish: &mut FnMut(String, Vec<Report>))
+                            -> Vec<Report> {
+    // The module may have been imported by another imported module in which case there is no
+    // `import` of it in `source`
+    let range = import_span(source, &err.module)
+        .map(|span| byte_span_to_range(source, span, encoding))
+        .unwrap_or_else(Range::default);
+    let uri = match module_uri(thread, &err.module) {
+        Some(uri) => uri,
+        None => {
+            // Modules which are not loaded from a file (such as those of the standard library)
+            // have nowhere to show their errors so the whole error is reported on the `import`
+            let mut report = Report::error("import-error", format!("{}", err));
+            report.diagnostic.range = range;
+            return vec![report];
         }
     };
-    let r = format!(r#"{{
-                        "jsonrpc": "2.0",
-                        "method": "textDocument/publishDiagnostics",
-                        "params": {}
-                    }}"#,
-                    to_value(&PublishDiagnosticsParams {
-                        uri: filename.into(),
-                        diagnostics: diagnostics,
+    let ImportError { module, source: imported_source, error } = err;
+    let mut imported_diagnostics = module_error_diagnostics(thread,
+                                                            settings,
+                                                            encoding,
+                                                            &uri,
+                                                            &imported_source,
+                                                            error,
+                                                            publish);
+    imported_diagnostics.truncate(settings.max_number_of_problems);
+
+    let mut report = Report::error("import-error",
+                                   format!("Error in imported module `{}`", module));
+    report.diagnostic.range = range;
+    report.related.extend(imported_diagnostics.first().map(|imported| {
+        RelatedInformation {
+            location: Location {
+                uri: uri.clone(),
+                range: imported.diagnostic.range,
+            },
+            message: imported.diagnostic.message.clone(),
+        }
+    }));
+    publish(uri, imported_diagnostics);
+    vec![report]
+}
+
+/// Finds the span of an `import` of `module` in `source`, from `import` to the end of the string
+/// literal naming the module
+fn import_span(source: &str, module: &str) -> Option<Span<BytePos>> {
+    let is_ident_char = |c: char| c.is_alphanumeric() || c == '_';
+    source.match_indices("import")
+        .filter_map(|(start, keyword)| {
+            if source[..start].chars().next_back().map_or(false, &is_ident_char) {
+                return None;
+            }
+            let rest = &source[start + keyword.len()..];
+            if rest.chars().next().map_or(false, &is_ident_char) {
+                return None;
+            }
+            let literal =
+                rest.trim_left_matches(|c: char| c.is_whitespace() || c == '!' || c == '(');
+            if !literal.starts_with('"') {
+                return None;
+            }
+            let name_start = source.len() - literal.len() + 1;
+            let name_end = match source[name_start..].find('"') {
+                Some(len) => name_start + len,
+                None => return None,
+            };
+            let name = &source[name_start..name_end];
+            if name == module || filename_to_module(name) == module {
+                Some(Span::new(BytePos::from(start), BytePos::from(name_end + 1)))
+            } else {
+                None
+            }
+        })
+        .next()
+}
+
+/// Runs the lints enabled in `lints` over the module at `uri`, which must just have been
+/// typechecked without errors
+fn lint_diagnostics(thread: &Thread,
+                    uri: &str,
+                    source: &str,
+                    lints: &Lints,
+                    encoding: PositionEncoding)
+                    -> Vec<Report> {
+    let import = thread.get_macros().get("import").expect("Import macro");
+    let import = import.downcast_ref::<Import<CheckImporter>>().expect("Check importer");
+    let importer = import.importer.0.lock().unwrap();
+    let module = match importer.get(&strip_file_prefix(thread, uri)) {
+        Some(module) => module,
+        None => return vec![],
+    };
+    lint::lint(source, &module.expr, lints)
+        .into_iter()
+        .map(|lint| {
+            let mut report = Report::warning(lint.code, lint.message);
+            report.diagnostic.range = module.lines.range(source, lint.span, encoding);
+            report.related.extend(lint.related.map(|(span, message)| {
+                RelatedInformation {
+                    location: Location {
+                        uri: uri.into(),
+                        range: module.lines.range(source, span, encoding),
+                    },
+                    message: message,
+                }
+            }));
+            report
+        })
+        .collect()
+}
+
+/// Converts `err`, found while checking the module at `uri`, to diagnostics. If the module
+/// typechecked far enough to produce `expr` it is used to find locations related to the errors.
+fn error_diagnostics(uri: &str,
+                     source: &str,
+                     expr: Option<&SpannedExpr<Symbol>>,
+                     err: GluonError,
+                     encoding: PositionEncoding)
+                     -> Vec<Report> {
+    let lines = Lines::new(source);
+    match err {
+        GluonError::Typecheck(err) => {
+            err.errors()
+                .errors
+                .into_iter()
+                // An `import` which fails to expand is left in the expression where the
+                // typechecker reports it again, the macro error already describes it
+                .filter(|err| match err.value {
+                    TypeError::UndefinedVariable(ref id) => query::name(id) != "import",
+                    _ => true,
+                })
+                .map(|err| {
+                    let mut report = Report::error(diagnostic::type_error_code(&err.value),
+                                                   format!("{}", err.value));
+                    report.diagnostic.range = lines.location_range(source, &err.span, encoding);
+                    let span = Span::new(err.span.start.absolute, err.span.end.absolute);
+                    let binding = match (&err.value, expr) {
+                        (&TypeError::Unification(..), Some(expr)) => {
+                            diagnostic::expected_type_binding(source, expr, span)
+                        }
+                        _ => None,
+                    };
+                    report.related.extend(binding.map(|(name, span)| {
+                        RelatedInformation {
+                            location: Location {
+                                uri: uri.into(),
+                                range: lines.range(source, span, encoding),
+                            },
+                            message: format!("The expected type comes from `{}`", name),
+                        }
                     }));
-    print!("Content-Length: {}\r\n\r\n{}", r.len(), r);
+                    report
+                })
+                .collect()
+        }
+        GluonError::Parse(err) => {
+            err.errors()
+                .errors
+                .into_iter()
+                .map(|err| {
+                    let mut report = Report::error("parse-error", format!("{}", err));
+                    report.diagnostic.range = lines.location_range(source, &err.span, encoding);
+                    report
+                })
+                .collect()
+        }
+        GluonError::Macro(err) => vec![Report::error("macro-error", format!("{}", err))],
+        err => vec![Report::error("error", format!("{}", err))],
+    }
 }
 
-fn log_message(message: String) {
+fn publish_diagnostics(output: &Output, filename: &str, diagnostics: Vec<Report>) {
+    // Serialized by hand to include the `relatedInformation` of each diagnostic
+    let mut params = BTreeMap::new();
+    params.insert("uri".to_string(), Value::String(filename.into()));
+    params.insert("diagnostics".to_string(),
+                  Value::Array(diagnostics.iter().map(Report::to_value).collect()));
+    if let Err(err) = output.notify("textDocument/publishDiagnostics", &params) {
+        error!("Unable to publish diagnostics: {}", err);
+    }
+}
+
+fn log_message(output: &Output, message: String) {
     debug!("{}", message);
-    let r = format!(r#"{{"jsonrpc": "2.0", "method": "window/logMessage", "params": {} }}"#,
-                    to_value(&LogMessageParams {
-                        typ: MessageType::Log,
-                        message: message,
-                    }));
-    print!("Content-Length: {}\r\n\r\n{}", r.len(), r);
+    let params = LogMessageParams {
+        typ: MessageType::Log,
+        message: message,
+    };
+    if let Err(err) = output.notify("window/logMessage", &params) {
+        error!("Unable to log message: {}", err);
+    }
 }
 
 pub fn read_message<R>(mut reader: R) -> Result<Option<String>, Box<StdError>>
@@ -485,53 +1836,294 @@ pub fn read_message<R>(mut reader: R) -> Result<Option<String>, Box<StdError>>
     }
 }
 
-fn main_loop(io: &mut IoHandler, exit_token: Arc<AtomicBool>) -> Result<(), Box<StdError>> {
-    let stdin = io::stdin();
+/// Returns the method and the id (serialized to a string) of a json-rpc message
+fn message_method_and_id(json: &str) -> (Option<String>, Option<String>) {
+    let value: Value = match serde_json::from_str(json) {
+        Ok(value) => value,
+        Err(_) => return (None, None),
+    };
+    let object = match value.as_object() {
+        Some(object) => object,
+        None => return (None, None),
+    };
+    (object.get("method").and_then(|method| method.as_str()).map(String::from),
+     object.get("id").map(|id| id.to_string()))
+}
+
+/// Returns whether `json` is a response, that is an object with an `id` and a `result` or `error`
+/// but no `method`. Anything else is left to the `IoHandler` which reports invalid messages.
+fn is_response(json: &str) -> bool {
+    serde_json::from_str::<Value>(json)
+        .ok()
+        .and_then(|value| {
+            value.as_object().map(|message| {
+                message.contains_key("id") && !message.contains_key("method") &&
+                (message.contains_key("result") || message.contains_key("error"))
+            })
+        })
+        .unwrap_or(false)
+}
+
+/// Returns the id of the request which a `$/cancelRequest` notification cancels
+fn cancel_request_id(json: &str) -> Option<String> {
+    serde_json::from_str::<Value>(json)
+        .ok()
+        .and_then(|value| {
+            value.as_object()
+                .and_then(|message| message.get("params"))
+                .and_then(|params| params.as_object())
+                .and_then(|params| params.get("id"))
+                .map(|id| id.to_string())
+        })
+}
+
+/// Reads messages on a separate thread so that `$/cancelRequest` notifications are seen while the
+/// main loop is busy handling earlier requests
+fn spawn_reader<R>(mut input: R,
+                   cancellation: Cancellation,
+                   output: Output)
+                   -> mpsc::Receiver<String>
+    where R: BufRead + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    ::std::thread::spawn(move || {
+        loop {
+            let json = match read_message(&mut input) {
+                Ok(Some(json)) => json,
+                Ok(None) => return,
+                Err(err) => {
+                    log_message(&output, format!("Unable to read message: {}", err));
+                    return;
+                }
+            };
+            match message_method_and_id(&json) {
+                (Some(ref method), _) if method == "$/cancelRequest" => {
+                    if let Some(id) = cancel_request_id(&json) {
+                        cancellation.cancel(&id);
+                    }
+                    continue;
+                }
+                (Some(_), Some(ref id)) => cancellation.register(id),
+                _ => (),
+            }
+            if sender.send(json).is_err() {
+                return;
+            }
+        }
+    });
+    receiver
+}
+
+fn main_loop<R>(io: &mut IoHandler,
+                input: R,
+                cancellation: &Cancellation,
+                worker: &CheckWorker,
+                output: &Output,
+                exit_token: Arc<AtomicBool>)
+                -> Result<(), Box<StdError>>
+    where R: BufRead + Send + 'static,
+{
+    let messages = spawn_reader(input, cancellation.clone(), output.clone());
     while !exit_token.load(atomic::Ordering::SeqCst) {
-        match try!(read_message(stdin.lock())) {
-            Some(json) => {
-                debug!("Handle: {}", json);
-                if let Some(response) = io.handle_request_sync(&json) {
-                    print!("Content-Length: {}\r\n\r\n{}", response.len(), response);
-                    try!(io::stdout().flush());
+        let json = match messages.recv() {
+            Ok(json) => json,
+            Err(_) => return Ok(()),
+        };
+        debug!("Handle: {}", json);
+        if is_response(&json) {
+            // A response to a request sent by the server, which are all fire and forget
+            debug!("Ignoring response: {}", json);
+            continue;
+        }
+        let (_, id) = message_method_and_id(&json);
+        let token = cancellation.start(id.as_ref().map(|id| &id[..]));
+        if id.is_some() {
+            // Documents are checked in the background but positions in requests refer to the
+            // latest text so the pending checks need to finish first
+            worker.wait_for_checks(&token);
+        }
+        // Requests which were cancelled before being handled are not run at all
+        let response = if token.is_cancelled() {
+            None
+        } else {
+            io.handle_request_sync(&json)
+        };
+        cancellation.finish(id.as_ref().map(|id| &id[..]));
+        match id {
+            Some(ref id) if token.is_cancelled() => try!(send_cancelled(output, id)),
+            _ => {
+                if let Some(response) = response {
+                    try!(output.write(&response));
                 }
             }
-            None => return Ok(()),
         }
     }
     Ok(())
 }
 
-pub fn run() {
-    ::env_logger::init().unwrap();
-    let handle = ::std::thread::spawn(|| {
-        let thread = new_vm();
-        let import = Import::new(CheckImporter::new());
-        thread.get_macros().insert("import".into(), import);
+/// Creates a vm whose `import` macro typechecks modules instead of compiling them
+fn new_check_vm(import_paths: Vec<PathBuf>) -> RootedThread {
+    let thread = new_vm();
+    let import = Import::new(CheckImporter::new());
+    import.paths.write().unwrap().extend(import_paths);
+    thread.get_macros().insert("import".into(), import);
+    thread
+}
+
+fn send_cancelled(output: &Output, id: &str) -> io::Result<()> {
+    output.respond_error(id, cancellation::REQUEST_CANCELLED, "Request cancelled")
+}
+
+/// Runs the language server over the transport in `config`
+pub fn run(config: Config) -> io::Result<()> {
+    try!(config::init_logger(&config));
+    match config.transport {
+        Transport::Stdio => {
+            serve(&config, io::BufReader::new(io::stdin()), io::stdout());
+            Ok(())
+        }
+        Transport::Listen(ref addr) => transport::listen(&config, addr),
+        Transport::Connect(ref addr) => transport::connect(&config, addr),
+    }
+}
+
+/// Runs a language server which reads messages from `input` and writes messages to `output`.
+/// Returns when the client sends `exit` or disconnects.
+pub fn serve<R, W>(config: &Config, input: R, output: W)
+    where R: BufRead + Send + 'static,
+          W: io::Write + Send + 'static,
+{
+    let output = Output::new(output);
+    let import_paths = config.import_paths.clone();
+    let server_output = output.clone();
+    let handle = ::std::thread::spawn(move || {
+        let output = server_output;
+        let workspaces = Workspaces::new(import_paths);
+        let documents = DocumentStore::new();
+        let settings = SharedSettings::new();
+        let encoding = SharedEncoding::new();
+        let cancellation = Cancellation::new();
+        let worker = CheckWorker::spawn(workspaces.clone(),
+                                        documents.clone(),
+                                        settings.clone(),
+                                        encoding.clone(),
+                                        output.clone());
+
+        let watch_files = Arc::new(AtomicBool::new(false));
 
         let mut io = IoHandler::new();
-        io.add_method("initialize", ServerCommand(Initialize(thread.clone())));
+        io.add_method("initialize",
+                      ServerCommand(Initialize(workspaces.clone(),
+                                               encoding.clone(),
+                                               worker.clone(),
+                                               watch_files.clone())));
         io.add_method("textDocument/completion",
-                      ServerCommand(Completion(thread.clone())));
+                      ServerCommand(Completion(workspaces.clone(),
+                                               settings.clone(),
+                                               cancellation.clone(),
+                                               encoding.clone())));
         io.add_method("textDocument/hover",
-                      ServerCommand(HoverCommand(thread.clone())));
+                      ServerCommand(HoverCommand(workspaces.clone(),
+                                                 settings.clone(),
+                                                 encoding.clone())));
+        io.add_method("textDocument/signatureHelp",
+                      ServerCommand(SignatureHelpCommand(workspaces.clone(),
+                                                         settings.clone(),
+                                                         encoding.clone())));
+        io.add_method("textDocument/definition",
+                      ServerCommand(Definition(workspaces.clone(), encoding.clone())));
+        io.add_method("textDocument/references",
+                      ServerCommand(References(workspaces.clone(),
+                                               cancellation.clone(),
+                                               encoding.clone())));
+        io.add_method("textDocument/prepareRename",
+                      ServerCommand(PrepareRename(workspaces.clone(), encoding.clone())));
+        io.add_method("textDocument/rename",
+                      ServerCommand(Rename(workspaces.clone(),
+                                           cancellation.clone(),
+                                           encoding.clone())));
+        io.add_method("textDocument/documentSymbol",
+                      ServerCommand(DocumentSymbols(workspaces.clone(), encoding.clone())));
+        io.add_method("workspace/symbol",
+                      ServerCommand(WorkspaceSymbols(workspaces.clone(),
+                                                     documents.clone(),
+                                                     cancellation.clone(),
+                                                     encoding.clone())));
+        io.add_method("textDocument/formatting",
+                      ServerCommand(Formatting(workspaces.clone(),
+                                               documents.clone(),
+                                               settings.clone(),
+                                               encoding.clone())));
+        io.add_method("textDocument/rangeFormatting",
+                      ServerCommand(RangeFormatting(workspaces.clone(),
+                                                    documents.clone(),
+                                                    settings.clone(),
+                                                    encoding.clone())));
         io.add_method("shutdown", |_| Ok(Value::I64(0)));
         let exit_token = Arc::new(AtomicBool::new(false));
         let exit_token2 = exit_token.clone();
         io.add_notification("exit",
                             move |_| exit_token.store(true, atomic::Ordering::SeqCst));
         io.add_notification("textDocument/didOpen",
-                            ServerCommand(TextDocumentDidOpen(thread.clone())));
+                            ServerNotification(TextDocumentDidOpen(documents.clone(),
+                                                                   worker.clone()),
+                                               output.clone()));
         io.add_notification("textDocument/didChange",
-                            ServerCommand(TextDocumentDidChange(thread)));
+                            ServerNotification(TextDocumentDidChange(documents.clone(),
+                                                                     worker.clone(),
+                                                                     output.clone(),
+                                                                     encoding.clone()),
+                                               output.clone()));
+        io.add_notification("textDocument/didClose",
+                            ServerNotification(TextDocumentDidClose(workspaces.clone(),
+                                                                    documents.clone(),
+                                                                    worker.clone(),
+                                                                    output.clone()),
+                                               output.clone()));
+        io.add_notification("textDocument/didSave",
+                            ServerNotification(TextDocumentDidSave(documents.clone(),
+                                                                   worker.clone(),
+                                                                   output.clone()),
+                                               output.clone()));
+        let initialized_output = output.clone();
+        io.add_notification("initialized", move |_| {
+            // Clients which can not register capabilities dynamically reject the request
+            if watch_files.load(atomic::Ordering::SeqCst) {
+                register_file_watcher(&initialized_output);
+            }
+        });
+        io.add_notification("workspace/didChangeWatchedFiles",
+                            ServerNotification(DidChangeWatchedFiles(workspaces.clone(),
+                                                                     documents.clone(),
+                                                                     worker.clone(),
+                                                                     output.clone()),
+                                               output.clone()));
+        io.add_notification("workspace/didChangeWorkspaceFolders",
+                            ServerNotification(DidChangeWorkspaceFolders(workspaces.clone(),
+                                                                         documents.clone(),
+                                                                         worker.clone()),
+                                               output.clone()));
+        io.add_notification("workspace/didChangeConfiguration",
+                            ServerNotification(DidChangeConfiguration(workspaces,
+                                                                      documents,
+                                                                      settings,
+                                                                      worker.clone()),
+                                               output.clone()));
 
-        main_loop(&mut io, exit_token2).unwrap();
+        if let Err(err) = main_loop(&mut io,
+                                    input,
+                                    &cancellation,
+                                    &worker,
+                                    &output,
+                                    exit_token2) {
+            error!("Stopping the server: {}", err);
+        }
     });
     if let Err(err) = handle.join() {
         let msg = err.downcast_ref::<&'static str>()
             .cloned()
             .or_else(|| err.downcast_ref::<String>().map(|s| &s[..]))
             .unwrap_or("Any");
-        log_message(format!("Panic: `{}`", msg));
+        log_message(&output, format!("Panic: `{}`", msg));
     }
 }
@@ -0,0 +1,190 @@
+//! Lints which report likely mistakes in a module which typechecks
+
+use gluon::base::ast::{Expr, Pattern, SpannedExpr};
+use gluon::base::pos::{BytePos, Span};
+use gluon::base::symbol::Symbol;
+use gluon::filename_to_module;
+
+use query::{self, Occurrence, OccurrenceKind};
+use settings::Lints;
+
+/// A problem found by a lint
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lint {
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span<BytePos>,
+    /// Another span which explains the lint along with a message describing it
+    pub related: Option<(Span<BytePos>, String)>,
+}
+
+/// Runs the lints enabled in `lints` over `expr`, returning the lints in source order
+pub fn lint(source: &str, expr: &SpannedExpr<Symbol>, lints: &Lints) -> Vec<Lint> {
+    let occurrences = query::occurrences(source, expr);
+    let mut found = Vec::new();
+    walk(source, expr, &occurrences, lints, &mut found);
+    if lints.shadowing {
+        shadowing(&occurrences, &mut found);
+    }
+    found.sort_by_key(|lint| lint.span.start.to_usize());
+    found
+}
+
+fn walk(source: &str,
+        expr: &SpannedExpr<Symbol>,
+        occurrences: &[Occurrence],
+        lints: &Lints,
+        found: &mut Vec<Lint>) {
+    // Only the module which the implicit prelude wraps is part of the source
+    if query::is_inserted(expr.span) {
+        match expr.value {
+            Expr::LetBindings(_, ref body) |
+            Expr::TypeBindings(_, ref body) => walk(source, body, occurrences, lints, found),
+            _ => (),
+        }
+        return;
+    }
+    match expr.value {
+        Expr::LetBindings(ref bindings, _) => {
+            for bind in bindings {
+                let is_import = is_import(source, &bind.expr);
+                let enabled = if is_import {
+                    lints.unused_import
+                } else {
+                    lints.unused_binding
+                };
+                if enabled {
+                    for symbol in pattern_symbols(&bind.name.value) {
+                        unused(symbol, is_import, occurrences, found);
+                    }
+                }
+            }
+        }
+        Expr::Match(_, ref alts) if lints.unreachable_pattern => {
+            let mut catch_all = None;
+            let mut constructors: Vec<(&str, Span<BytePos>)> = Vec::new();
+            for alt in alts {
+                let name = match alt.pattern.value {
+                    Pattern::Constructor(ref ctor, _) => Some(query::name(&ctor.name)),
+                    _ => None,
+                };
+                let covering = catch_all.or_else(|| {
+                    constructors.iter()
+                        .find(|&&(constructor, _)| Some(constructor) == name)
+                        .map(|&(_, span)| span)
+                });
+                match covering {
+                    Some(span) => {
+                        found.push(Lint {
+                            code: "unreachable-pattern",
+                            message: "Unreachable pattern".into(),
+                            span: alt.pattern.span,
+                            related: Some((span, "Matched by this pattern".into())),
+                        })
+                    }
+                    None => {
+                        match name {
+                            Some(name) => constructors.push((name, alt.pattern.span)),
+                            // Identifiers and records match every value
+                            None => catch_all = Some(alt.pattern.span),
+                        }
+                    }
+                }
+            }
+        }
+        _ => (),
+    }
+    for sub_expr in query::sub_exprs(expr) {
+        walk(source, sub_expr, occurrences, lints, found);
+    }
+}
+
+/// Returns true if `expr` is an expanded `import`. An `import` expands to an identifier naming
+/// the module which keeps the span of the `import` and the string literal naming its file.
+fn is_import(source: &str, expr: &SpannedExpr<Symbol>) -> bool {
+    let (start, end) = (expr.span.start.to_usize(), expr.span.end.to_usize());
+    match expr.value {
+        Expr::Ident(ref id) if start <= end && end <= source.len() => {
+            let text = &source[start..end];
+            if !text.starts_with("import") {
+                return false;
+            }
+            let literal = text["import".len()..].trim_left();
+            literal.len() >= 2 && literal.starts_with('"') && literal.ends_with('"') &&
+            filename_to_module(&literal[1..literal.len() - 1]) == query::name(&id.name)
+        }
+        _ => false,
+    }
+}
+
+fn pattern_symbols(pattern: &Pattern<Symbol>) -> Vec<&Symbol> {
+    match *pattern {
+        Pattern::Ident(ref id) => vec![&id.name],
+        Pattern::Record { ref fields, .. } => {
+            fields.iter()
+                .map(|&(ref field, ref bind)| bind.as_ref().unwrap_or(field))
+                .collect()
+        }
+        Pattern::Constructor(_, ref args) => args.iter().map(|arg| &arg.name).collect(),
+    }
+}
+
+/// Reports the binding of `symbol` if it is never referred to within its scope. Names starting
+/// with `_` are expected to be unused and uppercase names (types and constructors) are skipped
+/// as uses of them in types are not tracked.
+fn unused(symbol: &Symbol, is_import: bool, occurrences: &[Occurrence], found: &mut Vec<Lint>) {
+    let name = query::name(symbol);
+    if name.starts_with('_') || name.chars().next().map_or(true, char::is_uppercase) {
+        return;
+    }
+    let binding = match query::binding(occurrences, symbol) {
+        Some(binding) => binding,
+        None => return,
+    };
+    let used = occurrences.iter().any(|occurrence| {
+        occurrence.kind == OccurrenceKind::Reference && occurrence.symbol == symbol &&
+        binding.scope.start <= occurrence.span.start &&
+        occurrence.span.end <= binding.scope.end
+    });
+    if !used {
+        let (code, message) = if is_import {
+            ("unused-import", format!("Unused import `{}`", name))
+        } else {
+            ("unused-binding", format!("Unused binding `{}`", name))
+        };
+        found.push(Lint {
+            code: code,
+            message: message,
+            span: binding.span,
+            related: None,
+        });
+    }
+}
+
+/// Reports bindings which shadow another binding of the same name
+fn shadowing(occurrences: &[Occurrence], found: &mut Vec<Lint>) {
+    let bindings: Vec<_> = occurrences.iter()
+        .filter(|occurrence| occurrence.kind == OccurrenceKind::Binding)
+        .collect();
+    for binding in &bindings {
+        let name = query::name(binding.symbol);
+        if name.starts_with('_') {
+            continue;
+        }
+        let shadowed = bindings.iter()
+            .filter(|other| {
+                query::name(other.symbol) == name && other.span.start < binding.span.start &&
+                other.scope.start <= binding.span.start &&
+                binding.span.end <= other.scope.end
+            })
+            .max_by_key(|other| other.span.start.to_usize());
+        if let Some(shadowed) = shadowed {
+            found.push(Lint {
+                code: "shadowing",
+                message: format!("`{}` shadows an earlier binding", name),
+                span: binding.span,
+                related: Some((shadowed.span, format!("The shadowed `{}`", name))),
+            });
+        }
+    }
+}
@@ -1,6 +1,113 @@
+extern crate log;
 
 extern crate gluon_language_server;
 
+use std::env;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+
+use log::LogLevelFilter;
+
+use gluon_language_server::{CheckFormat, Config, Transport};
+
+const USAGE: &'static str = "\
+Usage: gluon_language-server [OPTIONS]
+       gluon_language-server check [OPTIONS] <PATH>...
+
+Commands:
+    check                 Typecheck the files (or directories of files) in <PATH>... and print
+                          any errors. Exits with a non-zero status if errors were found.
+
+Options:
+    --stdio               Communicate over stdin and stdout (the default)
+    --listen <addr>       Accept clients on a TCP address, serving one at a time
+    --connect <addr>      Connect to a client listening on a TCP address
+    --import-path <path>  Add a directory which `import` searches for modules (can be repeated)
+    --log-file <path>     Write log messages to a file instead of stderr
+    --log-level <level>   Only log messages at this level or above
+                          (off, error, warn, info, debug or trace)
+    --quiet               Do not log anything, same as `--log-level off`
+    --json                Print the errors found by `check` as json
+    --version             Print version information and exit
+    --help                Print this message and exit
+";
+
+enum Command {
+    Help,
+    Version,
+    Run(Config),
+    Check(Config, Vec<PathBuf>, CheckFormat),
+}
+
+fn parse_args<I>(args: I) -> Result<Command, String>
+    where I: IntoIterator<Item = String>,
+{
+    let mut config = Config::default();
+    let mut args = args.into_iter().peekable();
+    let check = args.peek().map_or(false, |arg| arg == "check");
+    if check {
+        args.next();
+    }
+    let mut paths = Vec::new();
+    let mut format = CheckFormat::Human;
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next().ok_or_else(|| format!("Expected a value after `{}`", name))
+        };
+        match &arg[..] {
+            "--help" | "-h" => return Ok(Command::Help),
+            "--version" | "-V" => return Ok(Command::Version),
+            "--stdio" => config.transport = Transport::Stdio,
+            "--listen" => config.transport = Transport::Listen(try!(value(&arg))),
+            "--connect" => config.transport = Transport::Connect(try!(value(&arg))),
+            "--import-path" => config.import_paths.push(PathBuf::from(try!(value(&arg)))),
+            "--log-file" => config.log_file = Some(PathBuf::from(try!(value(&arg)))),
+            "--log-level" => {
+                let level = try!(value(&arg));
+                config.log_level = Some(try!(level.parse::<LogLevelFilter>()
+                    .map_err(|_| format!("Invalid log level `{}`", level))));
+            }
+            "--quiet" | "-q" => config.log_level = Some(LogLevelFilter::Off),
+            "--json" if check => format = CheckFormat::Json,
+            _ if check && !arg.starts_with('-') => paths.push(PathBuf::from(&arg)),
+            _ => return Err(format!("Unknown argument `{}`", arg)),
+        }
+    }
+    if check {
+        if paths.is_empty() {
+            return Err("Expected at least one path to check".into());
+        }
+        Ok(Command::Check(config, paths, format))
+    } else {
+        Ok(Command::Run(config))
+    }
+}
+
 fn main() {
-    gluon_language_server::run();
+    let result = match parse_args(env::args().skip(1)) {
+        Ok(Command::Help) => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        Ok(Command::Version) => {
+            println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+            Ok(())
+        }
+        Ok(Command::Run(config)) => {
+            gluon_language_server::run(config).map_err(|err| err.to_string())
+        }
+        Ok(Command::Check(config, paths, format)) => {
+            match gluon_language_server::check_files(&config, &paths, format) {
+                Ok(0) => Ok(()),
+                Ok(_) => process::exit(1),
+                Err(err) => Err(err.to_string()),
+            }
+        }
+        Err(err) => Err(format!("{}\n\n{}", err, USAGE)),
+    };
+    if let Err(err) = result {
+        let _ = writeln!(io::stderr(), "{}", err);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use serde_json::{self, Value};
+use serde_json::value::to_value;
+
+/// Sends messages to the client.
+///
+/// Every message is framed and flushed while holding a lock so messages sent from different
+/// threads never interleave.
+#[derive(Clone)]
+pub struct Output(Arc<Mutex<Box<Write + Send>>>);
+
+impl Output {
+    pub fn new<W>(writer: W) -> Output
+        where W: Write + Send + 'static,
+    {
+        Output(Arc::new(Mutex::new(Box::new(writer))))
+    }
+
+    /// Sends a notification with `method` and `params`
+    pub fn notify<P>(&self, method: &str, params: &P) -> io::Result<()>
+        where P: Serialize,
+    {
+        let mut message = BTreeMap::new();
+        message.insert("jsonrpc".to_string(), Value::String("2.0".into()));
+        message.insert("method".to_string(), Value::String(method.into()));
+        message.insert("params".to_string(), to_value(params));
+        self.send(&Value::Object(message))
+    }
+
+    /// Sends a request initiated by the server
+    pub fn request<P>(&self, id: u64, method: &str, params: &P) -> io::Result<()>
+        where P: Serialize,
+    {
+        let mut message = BTreeMap::new();
+        message.insert("jsonrpc".to_string(), Value::String("2.0".into()));
+        message.insert("id".to_string(), Value::U64(id));
+        message.insert("method".to_string(), Value::String(method.into()));
+        message.insert("params".to_string(), to_value(params));
+        self.send(&Value::Object(message))
+    }
+
+    /// Sends an error response to the request `id`. `id` is the request id as it appeared in the
+    /// request.
+    pub fn respond_error(&self, id: &str, code: i64, message: &str) -> io::Result<()> {
+        let id = try!(serde_json::from_str(id)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err)));
+        let mut error = BTreeMap::new();
+        error.insert("code".to_string(), Value::I64(code));
+        error.insert("message".to_string(), Value::String(message.into()));
+        let mut response = BTreeMap::new();
+        response.insert("jsonrpc".to_string(), Value::String("2.0".into()));
+        response.insert("id".to_string(), id);
+        response.insert("error".to_string(), Value::Object(error));
+        self.send(&Value::Object(response))
+    }
+
+    pub fn send(&self, message: &Value) -> io::Result<()> {
+        let json = try!(serde_json::to_string(message)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)));
+        self.write(&json)
+    }
+
+    /// Sends a message which has already been serialized
+    pub fn write(&self, json: &str) -> io::Result<()> {
+        let mut writer = self.0.lock().unwrap();
+        try!(write!(writer, "Content-Length: {}\r\n\r\n{}", json.len(), json));
+        writer.flush()
+    }
+}
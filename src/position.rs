@@ -0,0 +1,231 @@
+//! Conversions between positions in gluon's source (byte offsets and `Location`s) and the zero
+//! based line and character `Position`s of the language server protocol.
+//!
+//! The `character` of a `Position` counts code units of the encoding negotiated with the client
+//! through `positionEncoding` (UTF-16 unless the client says otherwise) while gluon works with
+//! byte offsets into UTF-8 source so every conversion takes the `PositionEncoding` in use.
+
+use std::cmp;
+use std::sync::{Arc, RwLock};
+
+use gluon::base::pos::{BytePos, Location, Span};
+
+use languageserver_types::{Position, Range};
+
+/// What the `character` of a `Position` counts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PositionEncoding {
+    /// Bytes of UTF-8
+    Utf8,
+    /// Code units of UTF-16, the default of the protocol
+    Utf16,
+    /// Unicode scalar values (`char`s)
+    Utf32,
+}
+
+impl Default for PositionEncoding {
+    fn default() -> PositionEncoding {
+        PositionEncoding::Utf16
+    }
+}
+
+impl PositionEncoding {
+    /// The name of the encoding in `positionEncoding` and `general.positionEncodings`
+    pub fn name(self) -> &'static str {
+        match self {
+            PositionEncoding::Utf8 => "utf-8",
+            PositionEncoding::Utf16 => "utf-16",
+            PositionEncoding::Utf32 => "utf-32",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PositionEncoding> {
+        match name {
+            "utf-8" => Some(PositionEncoding::Utf8),
+            "utf-16" => Some(PositionEncoding::Utf16),
+            "utf-32" => Some(PositionEncoding::Utf32),
+            _ => None,
+        }
+    }
+
+    /// Picks the encoding to use from the encodings the client supports, preferring UTF-8 as it
+    /// needs no conversion. Clients which do not list any encodings only support UTF-16.
+    pub fn negotiate<'a, I>(supported: I) -> PositionEncoding
+        where I: IntoIterator<Item = &'a str>,
+    {
+        let supported: Vec<_> = supported.into_iter()
+            .filter_map(PositionEncoding::from_name)
+            .collect();
+        if supported.contains(&PositionEncoding::Utf8) {
+            PositionEncoding::Utf8
+        } else {
+            supported.first().cloned().unwrap_or_else(PositionEncoding::default)
+        }
+    }
+
+    /// Returns the number of code units in `text`
+    pub fn code_units(self, text: &str) -> usize {
+        match self {
+            PositionEncoding::Utf8 => text.len(),
+            _ => text.chars().map(|c| self.char_units(c)).sum(),
+        }
+    }
+
+    fn char_units(self, c: char) -> usize {
+        match self {
+            PositionEncoding::Utf8 => c.len_utf8(),
+            PositionEncoding::Utf16 => c.len_utf16(),
+            PositionEncoding::Utf32 => 1,
+        }
+    }
+
+    /// Returns the byte offset of the code unit `units` in `line`, clamped to the end of `line`.
+    /// Returns `None` if `units` points into the middle of a character.
+    fn byte_offset(self, line: &str, units: usize) -> Option<usize> {
+        if self == PositionEncoding::Utf8 {
+            let offset = cmp::min(units, line.len());
+            return if line.is_char_boundary(offset) {
+                Some(offset)
+            } else {
+                None
+            };
+        }
+        let mut count = 0;
+        for (offset, c) in line.char_indices() {
+            if count == units {
+                return Some(offset);
+            }
+            count += self.char_units(c);
+            if count > units {
+                return None;
+            }
+        }
+        Some(line.len())
+    }
+}
+
+/// The encoding negotiated with the client, shared between every command
+#[derive(Clone)]
+pub struct SharedEncoding(Arc<RwLock<PositionEncoding>>);
+
+impl SharedEncoding {
+    pub fn new() -> SharedEncoding {
+        SharedEncoding(Arc::new(RwLock::new(PositionEncoding::default())))
+    }
+
+    pub fn get(&self) -> PositionEncoding {
+        *self.0.read().unwrap()
+    }
+
+    pub fn set(&self, encoding: PositionEncoding) {
+        *self.0.write().unwrap() = encoding;
+    }
+}
+
+/// Returns the line of `text` starting at `line_start`, without the line break
+fn line_at(text: &str, line_start: usize) -> &str {
+    let line = &text[line_start..];
+    line.find('\n').map_or(line, |i| &line[..i])
+}
+
+/// Where each line of a text starts. Conversions look up the line in the index so only the line
+/// itself is scanned to count code units.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lines {
+    starts: Vec<usize>,
+}
+
+impl Lines {
+    pub fn new(text: &str) -> Lines {
+        Lines {
+            starts: Some(0)
+                .into_iter()
+                .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+                .collect(),
+        }
+    }
+
+    /// Converts `position` to a byte offset in `text`, the text the index was created from.
+    /// Positions past the end of a line are clamped to the end of that line. Returns `None` if the
+    /// line does not exist or if the position points into the middle of a character.
+    pub fn offset(&self,
+                  text: &str,
+                  position: &Position,
+                  encoding: PositionEncoding)
+                  -> Option<usize> {
+        let line_start = match self.starts.get(position.line as usize) {
+            Some(&line_start) => line_start,
+            None => return None,
+        };
+        let line = line_at(text, line_start);
+        encoding.byte_offset(line, position.character as usize).map(|offset| line_start + offset)
+    }
+
+    /// Converts a byte offset in `text`, the text the index was created from, to a position.
+    /// Offsets past the end of `text` are clamped to the end of `text`.
+    pub fn position(&self, text: &str, offset: usize, encoding: PositionEncoding) -> Position {
+        let offset = cmp::min(offset, text.len());
+        let line = match self.starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+        Position {
+            line: line as u64,
+            character: encoding.code_units(&text[self.starts[line]..offset]) as u64,
+        }
+    }
+
+    pub fn range(&self, text: &str, span: Span<BytePos>, encoding: PositionEncoding) -> Range {
+        Range {
+            start: self.position(text, span.start.to_usize(), encoding),
+            end: self.position(text, span.end.to_usize(), encoding),
+        }
+    }
+
+    /// Converts the span of an error (which may be from the parser or the typechecker) to a
+    /// range. Only the absolute byte position of each `Location` is used so the result does not
+    /// depend on how lines and columns are numbered.
+    pub fn location_range(&self,
+                          text: &str,
+                          span: &Span<Location>,
+                          encoding: PositionEncoding)
+                          -> Range {
+        self.range(text, Span::new(span.start.absolute, span.end.absolute), encoding)
+    }
+}
+
+/// Converts `position` to a byte offset in `text`, see `Lines::offset`. Every call indexes the
+/// whole of `text` so use `Lines` directly to convert several positions of the same text.
+pub fn position_to_offset(text: &str,
+                          position: &Position,
+                          encoding: PositionEncoding)
+                          -> Option<usize> {
+    Lines::new(text).offset(text, position, encoding)
+}
+
+/// Converts a byte offset in `text` to a position, see `Lines::position`
+pub fn offset_to_position(text: &str, offset: usize, encoding: PositionEncoding) -> Position {
+    Lines::new(text).position(text, offset, encoding)
+}
+
+pub fn position_to_byte_pos(text: &str,
+                            position: &Position,
+                            encoding: PositionEncoding)
+                            -> Option<BytePos> {
+    position_to_offset(text, position, encoding).map(BytePos::from)
+}
+
+pub fn byte_pos_to_position(text: &str, pos: BytePos, encoding: PositionEncoding) -> Position {
+    offset_to_position(text, pos.to_usize(), encoding)
+}
+
+pub fn byte_span_to_range(text: &str, span: Span<BytePos>, encoding: PositionEncoding) -> Range {
+    Lines::new(text).range(text, span, encoding)
+}
+
+pub fn location_span_to_range(text: &str,
+                              span: &Span<Location>,
+                              encoding: PositionEncoding)
+                              -> Range {
+    Lines::new(text).location_range(text, span, encoding)
+}
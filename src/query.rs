@@ -0,0 +1,706 @@
+use std::cmp;
+use std::mem;
+
+use gluon::base::ast::{Expr, Pattern, SpannedExpr, SpannedPattern};
+use gluon::base::fnv::FnvMap;
+use gluon::base::pos::{BytePos, Span, UNKNOWN_EXPANSION};
+use gluon::base::symbol::Symbol;
+use gluon::base::types::{ArcType, Type};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OccurrenceKind {
+    /// The place where a variable is bound (let binding, argument, pattern)
+    Binding,
+    /// A use of a previously bound variable
+    Reference,
+    /// A record field name in a projection, record expression or record pattern
+    Field,
+}
+
+/// The record a name was projected or destructured from, if that record is a plain identifier.
+/// Identifiers bound directly to another identifier (`let m = import "m.glu"`) are followed to the
+/// identifier they alias.
+#[derive(Clone, Copy, Debug)]
+pub struct Origin<'a> {
+    pub record: &'a Symbol,
+    pub field: &'a Symbol,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Occurrence<'a> {
+    pub symbol: &'a Symbol,
+    pub span: Span<BytePos>,
+    pub kind: OccurrenceKind,
+    pub origin: Option<Origin<'a>>,
+    /// The region in which a binding is visible. For other kinds of occurrences this is the same
+    /// as `span`
+    pub scope: Span<BytePos>,
+    /// The type of the record a field belongs to, if it is known
+    pub record_type: Option<&'a ArcType>,
+    /// True for the names of types. Types do not carry spans in the expression so their uses are
+    /// found by searching the source for the name.
+    pub is_type: bool,
+}
+
+/// Returns the name of `symbol` as it was written in the source, without the `:Line x, Row y`
+/// suffix added to disambiguate shadowed variables
+pub fn name<S: ?Sized + AsRef<str>>(symbol: &S) -> &str {
+    let name: &str = symbol.as_ref();
+    name.split(':').next().unwrap_or(name)
+}
+
+/// Returns true if `span` belongs to code which the compiler inserted around the module, such as
+/// the implicit prelude, rather than to the source
+pub fn is_inserted(span: Span<BytePos>) -> bool {
+    span.expansion_id == UNKNOWN_EXPANSION
+}
+
+/// Returns the span of `expr` including the body of `let` and `type` bindings and the fields of
+/// records, which the parser leaves out of the span of the bindings and the record
+pub fn full_span(source: &str, expr: &SpannedExpr<Symbol>) -> Span<BytePos> {
+    match expr.value {
+        Expr::LetBindings(_, ref body) |
+        Expr::TypeBindings(_, ref body) => {
+            let body = full_span(source, body);
+            Span::new(cmp::min(expr.span.start, body.start),
+                      cmp::max(expr.span.end, body.end))
+        }
+        Expr::Record { ref exprs, .. } => {
+            let mut end = expr.span.end;
+            for (i, &(ref field, ref value)) in exprs.iter().enumerate() {
+                match *value {
+                    Some(ref value) if !is_punned(expr.span, value) => {
+                        end = cmp::max(end, full_span(source, value).end);
+                    }
+                    // Fields without a value are only spanned by their name in the source
+                    _ => {
+                        let field = name(field);
+                        let name_end = field_name_end(source, expr.span, exprs, i);
+                        if let Some(found) = find_word(source,
+                                                       field,
+                                                       end.to_usize(),
+                                                       name_end.to_usize()) {
+                            end = BytePos::from(found + field.len());
+                        }
+                    }
+                }
+            }
+            Span::new(expr.span.start, end)
+        }
+        _ => expr.span,
+    }
+}
+
+/// Returns true if `value` is the value of a field written without one (`{ x }`). The compiler
+/// fills in such fields with the variable of the same name, spanned by the record.
+pub fn is_punned(record: Span<BytePos>, value: &SpannedExpr<Symbol>) -> bool {
+    value.span.start <= record.start
+}
+
+/// Returns the position before which the name of the field at `index` of the record `exprs` is
+/// written. Records are only spanned by their opening brace so the name is bounded by the value
+/// of the field or, for fields without a value, by the value of a later field or the end of
+/// `source`.
+pub fn field_name_end(source: &str,
+                      record: Span<BytePos>,
+                      exprs: &[(Symbol, Option<SpannedExpr<Symbol>>)],
+                      index: usize)
+                      -> BytePos {
+    exprs[index..]
+        .iter()
+        .filter_map(|&(_, ref value)| value.as_ref())
+        .find(|value| !is_punned(record, value))
+        .map_or(BytePos::from(source.len()), |value| value.span.start)
+}
+
+/// Collects every identifier in `expr` along with its span in `source`.
+///
+/// Some identifiers such as function arguments do not carry a span of their own so those are
+/// located by searching `source` between the spans of the surrounding expressions.
+pub fn occurrences<'a>(source: &str, expr: &'a SpannedExpr<Symbol>) -> Vec<Occurrence<'a>> {
+    let mut walker = Walker {
+        source: source,
+        occurrences: Vec::new(),
+        scope: full_span(source, expr),
+        aliases: FnvMap::default(),
+        skipped: comments_and_strings(source, true),
+    };
+    walker.walk_expr(expr);
+    walker.occurrences
+}
+
+/// Returns the names of the fields of a record of type `typ`, sorted so that records with the same
+/// fields (which in gluon have the same type) give the same names. Returns `None` if `typ` is not
+/// known to be a record.
+pub fn record_fields(typ: &ArcType) -> Option<Vec<&str>> {
+    let mut typ = typ;
+    while let Type::Alias(ref alias) = **typ {
+        typ = &alias.typ;
+    }
+    match **typ {
+        Type::Record(_) => {
+            let mut fields: Vec<_> = typ.row_iter().map(|field| name(&field.name)).collect();
+            fields.sort();
+            Some(fields)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the innermost occurrence which contains `pos`
+pub fn occurrence_at<'a, 'b>(occurrences: &'b [Occurrence<'a>],
+                             pos: BytePos)
+                             -> Option<&'b Occurrence<'a>> {
+    occurrences.iter()
+        .filter(|occurrence| occurrence.span.start <= pos && pos <= occurrence.span.end)
+        .min_by_key(|occurrence| occurrence.span.end.to_usize() - occurrence.span.start.to_usize())
+}
+
+/// Returns the binding of `symbol`
+pub fn binding<'a, 'b>(occurrences: &'b [Occurrence<'a>],
+                       symbol: &Symbol)
+                       -> Option<&'b Occurrence<'a>> {
+    occurrences.iter()
+        .find(|occurrence| {
+            occurrence.kind == OccurrenceKind::Binding && occurrence.symbol == symbol
+        })
+}
+
+/// Finds the binding which is exported from a module as the field `field`.
+///
+/// Modules are expected to be a sequence of `let` and `type` bindings ending in a record so only
+/// the bindings along that spine are considered.
+pub fn exported_binding<'a, 'b>(expr: &'a SpannedExpr<Symbol>,
+                                occurrences: &'b [Occurrence<'a>],
+                                field: &str)
+                                -> Option<Span<BytePos>> {
+    let mut expr = expr;
+    let mut spine = Vec::new();
+    loop {
+        match expr.value {
+            Expr::LetBindings(ref bindings, ref body) => {
+                spine.extend(bindings.iter().map(|binding| binding.name.span));
+                expr = body;
+            }
+            Expr::TypeBindings(_, ref body) => expr = body,
+            _ => break,
+        }
+    }
+    let is_exported = match expr.value {
+        Expr::Record { ref exprs, .. } => exprs.iter().any(|&(ref id, _)| name(id) == field),
+        _ => false,
+    };
+    if !is_exported {
+        return None;
+    }
+    occurrences.iter()
+        .rev()
+        .filter(|occurrence| {
+            occurrence.kind == OccurrenceKind::Binding && name(occurrence.symbol) == field
+        })
+        .find(|occurrence| {
+            spine.iter().any(|span| {
+                span.start <= occurrence.span.start && occurrence.span.end <= span.end
+            })
+        })
+        .map(|occurrence| occurrence.span)
+        .or(Some(expr.span))
+}
+
+/// Returns the expressions directly contained in `expr`
+pub fn sub_exprs(expr: &SpannedExpr<Symbol>) -> Vec<&SpannedExpr<Symbol>> {
+    match expr.value {
+        Expr::Ident(_) |
+        Expr::Literal(_) => vec![],
+        Expr::App(ref func, ref args) => Some(&**func).into_iter().chain(args).collect(),
+        Expr::IfElse(ref pred, ref if_true, ref if_false) => {
+            vec![&**pred, &**if_true, &**if_false]
+        }
+        Expr::Match(ref expr, ref alts) => {
+            Some(&**expr).into_iter().chain(alts.iter().map(|alt| &alt.expr)).collect()
+        }
+        Expr::Infix(ref lhs, _, ref rhs) => vec![&**lhs, &**rhs],
+        Expr::LetBindings(ref bindings, ref body) => {
+            bindings.iter().map(|bind| &bind.expr).chain(Some(&**body)).collect()
+        }
+        Expr::Projection(ref expr, _, _) => vec![&**expr],
+        Expr::Array(ref array) => array.exprs.iter().collect(),
+        Expr::Record { ref exprs, .. } => {
+            exprs.iter().filter_map(|&(_, ref value)| value.as_ref()).collect()
+        }
+        Expr::Lambda(ref lambda) => vec![&*lambda.body],
+        Expr::Tuple(ref exprs) |
+        Expr::Block(ref exprs) => exprs.iter().collect(),
+        Expr::TypeBindings(_, ref body) => vec![&**body],
+    }
+}
+
+/// Finds the innermost function application which `pos` is in, returning the function being
+/// applied and the index of the argument at `pos`.
+///
+/// A function which is followed by whitespace up to `pos` is treated as an application without
+/// any arguments yet.
+pub fn call_at<'a>(source: &str,
+                   expr: &'a SpannedExpr<Symbol>,
+                   pos: BytePos)
+                   -> Option<(&'a SpannedExpr<Symbol>, usize)> {
+    let trailing_space = |end: BytePos| {
+        end <= pos && pos.to_usize() <= source.len() &&
+        source[end.to_usize()..pos.to_usize()].chars().all(|c| c == ' ' || c == '\t')
+    };
+    // Every sub expression is searched as records and bindings are not spanned by their contents
+    for sub_expr in sub_exprs(expr) {
+        if let Some(call) = call_at(source, sub_expr, pos) {
+            return Some(call);
+        }
+    }
+    if pos < expr.span.start || (pos > expr.span.end && !trailing_space(expr.span.end)) {
+        return None;
+    }
+    match expr.value {
+        Expr::App(ref func, ref args) if pos > func.span.end => {
+            Some((&**func, args.iter().take_while(|arg| arg.span.end < pos).count()))
+        }
+        Expr::Ident(_) |
+        Expr::Projection(..) if pos > expr.span.end => Some((expr, 0)),
+        _ => None,
+    }
+}
+
+struct Walker<'a, 's> {
+    source: &'s str,
+    occurrences: Vec<Occurrence<'a>>,
+    /// The scope of any binding found while walking
+    scope: Span<BytePos>,
+    /// Identifiers which are bound to another identifier, mapped to the identifier they alias
+    aliases: FnvMap<&'a Symbol, &'a Symbol>,
+    /// Comments and string literals, which searches for identifiers skip
+    skipped: Vec<(usize, usize)>,
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn ident(expr: &SpannedExpr<Symbol>) -> Option<&Symbol> {
+    match expr.value {
+        Expr::Ident(ref id) => Some(&id.name),
+        _ => None,
+    }
+}
+
+impl<'a, 's> Walker<'a, 's> {
+    /// Returns the identifier `expr` refers to, following any aliases
+    fn record(&self, expr: &'a SpannedExpr<Symbol>) -> Option<&'a Symbol> {
+        ident(expr).map(|id| self.aliases.get(id).cloned().unwrap_or(id))
+    }
+
+    /// Runs `f` with `scope` as the scope of the bindings it finds
+    fn scoped<F>(&mut self, scope: Span<BytePos>, f: F)
+        where F: FnOnce(&mut Self),
+    {
+        let outer = mem::replace(&mut self.scope, scope);
+        f(self);
+        self.scope = outer;
+    }
+
+    fn push(&mut self,
+            symbol: &'a Symbol,
+            span: Span<BytePos>,
+            kind: OccurrenceKind,
+            origin: Option<Origin<'a>>) {
+        let scope = if kind == OccurrenceKind::Binding {
+            self.scope
+        } else {
+            span
+        };
+        self.occurrences.push(Occurrence {
+            symbol: symbol,
+            span: span,
+            kind: kind,
+            origin: origin,
+            scope: scope,
+            record_type: None,
+            is_type: false,
+        });
+    }
+
+    /// Finds the first occurrence of `symbol` between `start` and `end` which is not inside a
+    /// comment or string literal
+    fn find(&self, symbol: &Symbol, start: BytePos, end: BytePos) -> Option<Span<BytePos>> {
+        let mut start = start.to_usize();
+        while let Some(found) = find_word(self.source, name(symbol), start, end.to_usize()) {
+            let found_end = found + name(symbol).len();
+            match self.skipped.iter().find(|&&(s, e)| s <= found && found < e) {
+                Some(&(_, e)) => start = cmp::max(e, found_end),
+                None => return Some(Span::new(BytePos::from(found), BytePos::from(found_end))),
+            }
+        }
+        None
+    }
+
+    /// Searches for `symbol` in the source between `start` and `end`, returning the position
+    /// after the identifier if it was found or `start` otherwise
+    fn search(&mut self,
+              symbol: &'a Symbol,
+              start: BytePos,
+              end: BytePos,
+              kind: OccurrenceKind,
+              origin: Option<Origin<'a>>)
+              -> BytePos {
+        match self.find(symbol, start, end) {
+            Some(span) => {
+                self.push(symbol, span, kind, origin);
+                span.end
+            }
+            None => start,
+        }
+    }
+
+    /// Searches for the field `field` of a record of type `record_type`, see `search`
+    fn search_field(&mut self,
+                    field: &'a Symbol,
+                    start: BytePos,
+                    end: BytePos,
+                    origin: Option<Origin<'a>>,
+                    record_type: &'a ArcType)
+                    -> BytePos {
+        let found = self.search(field, start, end, OccurrenceKind::Field, origin);
+        if found != start {
+            if let Some(occurrence) = self.occurrences.last_mut() {
+                occurrence.record_type = Some(record_type);
+            }
+        }
+        found
+    }
+
+    /// Adds every occurrence of the type `symbol` between `start` and `end` which does not
+    /// overlap an already found occurrence as a reference
+    fn search_all(&mut self, symbol: &'a Symbol, start: BytePos, end: BytePos) {
+        let mut start = start;
+        while let Some(span) = self.find(symbol, start, end) {
+            start = span.end;
+            let overlaps = self.occurrences
+                .iter()
+                .any(|occurrence| {
+                    occurrence.span.start < span.end && span.start < occurrence.span.end
+                });
+            if !overlaps {
+                self.push(symbol, span, OccurrenceKind::Reference, None);
+                if let Some(occurrence) = self.occurrences.last_mut() {
+                    occurrence.is_type = true;
+                }
+            }
+        }
+    }
+
+    /// Walks `pattern` which is written somewhere before `end`. Patterns are only spanned by their
+    /// first token so `end` bounds the search for any names after it.
+    fn walk_pattern(&mut self,
+                    pattern: &'a SpannedPattern<Symbol>,
+                    end: BytePos,
+                    record: Option<&'a Symbol>) {
+        if is_inserted(pattern.span) {
+            return;
+        }
+        match pattern.value {
+            Pattern::Ident(ref id) => {
+                self.push(&id.name, pattern.span, OccurrenceKind::Binding, None);
+            }
+            Pattern::Constructor(ref ctor, ref args) => {
+                let mut start = self.search(&ctor.name,
+                                            pattern.span.start,
+                                            end,
+                                            OccurrenceKind::Reference,
+                                            None);
+                for arg in args {
+                    start = self.search(&arg.name, start, end, OccurrenceKind::Binding, None);
+                }
+            }
+            Pattern::Record { ref typ, ref fields, .. } => {
+                let mut start = pattern.span.start;
+                for &(ref field, ref bind) in fields {
+                    let origin = record.map(|record| {
+                        Origin {
+                            record: record,
+                            field: field,
+                        }
+                    });
+                    match *bind {
+                        // `{ x }` binds `x` just as `{ x = x }` does but the name is only written
+                        // once
+                        Some(ref bind) if self.is_punned(field, start, end) => {
+                            start = self.search(bind, start, end, OccurrenceKind::Binding, origin);
+                        }
+                        Some(ref bind) => {
+                            start = self.search_field(field, start, end, origin, typ);
+                            start = self.search(bind, start, end, OccurrenceKind::Binding, origin);
+                        }
+                        None => {
+                            start = self.search(field, start, end, OccurrenceKind::Binding, origin);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns true if the first `field` between `start` and `end` is not followed by `=`
+    fn is_punned(&self, field: &Symbol, start: BytePos, end: BytePos) -> bool {
+        self.find(field, start, end)
+            .map_or(false, |span| !self.source[span.end.to_usize()..].trim_left().starts_with('='))
+    }
+
+    fn walk_expr(&mut self, expr: &'a SpannedExpr<Symbol>) {
+        // Only the module which the implicit prelude wraps is part of the source
+        if is_inserted(expr.span) {
+            match expr.value {
+                Expr::LetBindings(_, ref body) |
+                Expr::TypeBindings(_, ref body) => self.walk_expr(body),
+                _ => (),
+            }
+            return;
+        }
+        match expr.value {
+            Expr::Ident(ref id) => {
+                self.push(&id.name, expr.span, OccurrenceKind::Reference, None);
+            }
+            Expr::Literal(_) => (),
+            Expr::App(ref func, ref args) => {
+                self.walk_expr(func);
+                for arg in args {
+                    self.walk_expr(arg);
+                }
+            }
+            Expr::IfElse(ref pred, ref if_true, ref if_false) => {
+                self.walk_expr(pred);
+                self.walk_expr(if_true);
+                self.walk_expr(if_false);
+            }
+            Expr::Match(ref expr, ref alts) => {
+                self.walk_expr(expr);
+                let record = self.record(expr);
+                for alt in alts {
+                    self.scoped(full_span(self.source, &alt.expr), |walker| {
+                        walker.walk_pattern(&alt.pattern, alt.expr.span.start, record)
+                    });
+                    self.walk_expr(&alt.expr);
+                }
+            }
+            Expr::Infix(ref lhs, ref op, ref rhs) => {
+                self.walk_expr(lhs);
+                self.search(&op.name,
+                            lhs.span.end,
+                            rhs.span.start,
+                            OccurrenceKind::Reference,
+                            None);
+                self.walk_expr(rhs);
+            }
+            Expr::LetBindings(ref bindings, ref body) => {
+                for bind in bindings {
+                    let record = self.record(&bind.expr);
+                    if let (&Pattern::Ident(ref id), Some(record)) = (&bind.name.value, record) {
+                        self.aliases.insert(&id.name, record);
+                    }
+                    self.scoped(full_span(self.source, expr), |walker| {
+                        walker.walk_pattern(&bind.name, bind.expr.span.start, record)
+                    });
+                    self.scoped(full_span(self.source, &bind.expr), |walker| {
+                        let mut start = bind.name.span.end;
+                        for arg in &bind.args {
+                            start = walker.search(&arg.name,
+                                                  start,
+                                                  bind.expr.span.start,
+                                                  OccurrenceKind::Binding,
+                                                  None);
+                        }
+                    });
+                    self.walk_expr(&bind.expr);
+                }
+                self.walk_expr(body);
+            }
+            Expr::Projection(ref record, ref field, _) => {
+                self.walk_expr(record);
+                let origin = self.record(record).map(|record| {
+                    Origin {
+                        record: record,
+                        field: field,
+                    }
+                });
+                match record.value {
+                    Expr::Ident(ref id) => {
+                        self.search_field(field, record.span.end, expr.span.end, origin, &id.typ);
+                    }
+                    _ => {
+                        self.search(field,
+                                    record.span.end,
+                                    expr.span.end,
+                                    OccurrenceKind::Field,
+                                    origin);
+                    }
+                }
+            }
+            Expr::Array(ref array) => {
+                for expr in &array.exprs {
+                    self.walk_expr(expr);
+                }
+            }
+            Expr::Record { ref typ, ref exprs, .. } => {
+                let mut start = expr.span.start;
+                for (i, &(ref field, ref value)) in exprs.iter().enumerate() {
+                    let end = field_name_end(self.source, expr.span, exprs, i);
+                    match *value {
+                        Some(ref value) if !is_punned(expr.span, value) => {
+                            self.search_field(field, start, end, None, typ);
+                            self.walk_expr(value);
+                            start = full_span(self.source, value).end;
+                        }
+                        // The name of the field is also a reference to the variable
+                        _ => {
+                            let symbol = value.as_ref().and_then(ident).unwrap_or(field);
+                            start = self.search(symbol,
+                                                start,
+                                                end,
+                                                OccurrenceKind::Reference,
+                                                None);
+                        }
+                    }
+                }
+            }
+            Expr::Lambda(ref lambda) => {
+                self.scoped(full_span(self.source, &lambda.body), |walker| {
+                    let mut start = expr.span.start;
+                    for arg in &lambda.args {
+                        start = walker.search(&arg.name,
+                                              start,
+                                              lambda.body.span.start,
+                                              OccurrenceKind::Binding,
+                                              None);
+                    }
+                });
+                self.walk_expr(&lambda.body);
+            }
+            Expr::Tuple(ref exprs) |
+            Expr::Block(ref exprs) => {
+                for expr in exprs {
+                    self.walk_expr(expr);
+                }
+            }
+            Expr::TypeBindings(ref bindings, ref body) => {
+                let first = self.occurrences.len();
+                let mut start = expr.span.start;
+                self.scoped(full_span(self.source, expr), |walker| {
+                    for bind in bindings {
+                        start = walker.search(&bind.name,
+                                              start,
+                                              expr.span.end,
+                                              OccurrenceKind::Binding,
+                                              None);
+                    }
+                });
+                for occurrence in &mut self.occurrences[first..] {
+                    occurrence.is_type = true;
+                }
+                let type_names: Vec<_> = self.occurrences[first..]
+                    .iter()
+                    .map(|occurrence| occurrence.symbol)
+                    .collect();
+                self.walk_expr(body);
+                // Types do not carry any spans so every other use of the name within the scope
+                // of the binding is assumed to refer to the type
+                for symbol in type_names {
+                    self.search_all(symbol, start, full_span(self.source, expr).end);
+                }
+            }
+        }
+    }
+}
+
+/// Finds the first occurrence of `word` in `source[start..end]` which is not part of a larger
+/// identifier
+pub fn find_word(source: &str, word: &str, start: usize, end: usize) -> Option<usize> {
+    if word.is_empty() || start > end || end > source.len() || !source.is_char_boundary(start) ||
+       !source.is_char_boundary(end) {
+        return None;
+    }
+    let is_ident = word.chars().all(is_ident_char);
+    let haystack = &source[start..end];
+    let mut offset = 0;
+    while let Some(i) = haystack[offset..].find(word) {
+        let found = offset + i;
+        let after = found + word.len();
+        let bounded = !is_ident ||
+                      (!haystack[..found].chars().next_back().map_or(false, is_ident_char) &&
+                       !haystack[after..].chars().next().map_or(false, is_ident_char));
+        if bounded {
+            return Some(start + found);
+        }
+        offset = after;
+    }
+    None
+}
+
+/// Returns the spans of the block comments and string literals in `source`, as well as those of
+/// the line comments if `line_comments` is set
+pub fn comments_and_strings(source: &str, line_comments: bool) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '/' if chars.peek().map(|&(_, c)| c) == Some('/') => {
+                let mut end = source.len();
+                while let Some(&(j, c)) = chars.peek() {
+                    if c == '\n' {
+                        end = j;
+                        break;
+                    }
+                    chars.next();
+                }
+                if line_comments {
+                    spans.push((i, end));
+                }
+            }
+            '/' if chars.peek().map(|&(_, c)| c) == Some('*') => {
+                chars.next();
+                let mut end = source.len();
+                let mut previous = ' ';
+                while let Some((j, c)) = chars.next() {
+                    if previous == '*' && c == '/' {
+                        end = j + 1;
+                        break;
+                    }
+                    previous = c;
+                }
+                spans.push((i, end));
+            }
+            '"' => {
+                let mut end = source.len();
+                while let Some((j, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => {
+                            end = j + 1;
+                            break;
+                        }
+                        _ => (),
+                    }
+                }
+                spans.push((i, end));
+            }
+            // Skip character literals so that `'"'` does not start a string
+            '\'' => {
+                if chars.peek().map(|&(_, c)| c) == Some('\\') {
+                    chars.next();
+                }
+                chars.next();
+                if chars.peek().map(|&(_, c)| c) == Some('\'') {
+                    chars.next();
+                }
+            }
+            _ => (),
+        }
+    }
+    spans
+}
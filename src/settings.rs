@@ -0,0 +1,137 @@
+use std::sync::{Arc, RwLock};
+
+use serde_json::Value;
+
+/// Language server features which can be turned off by the client
+#[derive(Clone, Debug, PartialEq)]
+pub struct Features {
+    pub diagnostics: bool,
+    pub completion: bool,
+    pub hover: bool,
+    pub signature_help: bool,
+    pub formatting: bool,
+}
+
+impl Default for Features {
+    fn default() -> Features {
+        Features {
+            diagnostics: true,
+            completion: true,
+            hover: true,
+            signature_help: true,
+            formatting: true,
+        }
+    }
+}
+
+/// Lints which report warnings, each can be turned off by the client
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lints {
+    pub unused_binding: bool,
+    pub unused_import: bool,
+    pub shadowing: bool,
+    pub unreachable_pattern: bool,
+}
+
+impl Default for Lints {
+    fn default() -> Lints {
+        Lints {
+            unused_binding: true,
+            unused_import: true,
+            // Shadowing is often done on purpose so it is only reported when asked for
+            shadowing: false,
+            unreachable_pattern: true,
+        }
+    }
+}
+
+/// The settings under the `gluon` section of the client's configuration
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// `gluon.maxNumberOfProblems`
+    pub max_number_of_problems: usize,
+    /// `gluon.importPaths`, searched in addition to the workspace root
+    pub import_paths: Vec<String>,
+    /// `gluon.features`
+    pub features: Features,
+    /// `gluon.lints`
+    pub lints: Lints,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            max_number_of_problems: 100,
+            import_paths: Vec::new(),
+            features: Features::default(),
+            lints: Lints::default(),
+        }
+    }
+}
+
+fn field<'a>(value: &'a Value, name: &str) -> Option<&'a Value> {
+    value.as_object().and_then(|object| object.get(name))
+}
+
+fn enabled(switches: &Value, name: &str, default: bool) -> bool {
+    field(switches, name).and_then(Value::as_bool).unwrap_or(default)
+}
+
+impl Settings {
+    /// Reads the settings sent with `workspace/didChangeConfiguration`. Settings which are
+    /// missing or have the wrong type are left at their defaults.
+    pub fn from_value(settings: &Value) -> Settings {
+        let settings = field(settings, "gluon").unwrap_or(settings);
+        let mut result = Settings::default();
+        if let Some(max) = field(settings, "maxNumberOfProblems").and_then(Value::as_u64) {
+            result.max_number_of_problems = max as usize;
+        }
+        if let Some(paths) = field(settings, "importPaths").and_then(Value::as_array) {
+            result.import_paths = paths.iter()
+                .filter_map(Value::as_str)
+                .map(String::from)
+                .collect();
+        }
+        if let Some(features) = field(settings, "features") {
+            let defaults = Features::default();
+            result.features = Features {
+                diagnostics: enabled(features, "diagnostics", defaults.diagnostics),
+                completion: enabled(features, "completion", defaults.completion),
+                hover: enabled(features, "hover", defaults.hover),
+                signature_help: enabled(features, "signatureHelp", defaults.signature_help),
+                formatting: enabled(features, "formatting", defaults.formatting),
+            };
+        }
+        if let Some(lints) = field(settings, "lints") {
+            let defaults = Lints::default();
+            result.lints = Lints {
+                unused_binding: enabled(lints, "unusedBinding", defaults.unused_binding),
+                unused_import: enabled(lints, "unusedImport", defaults.unused_import),
+                shadowing: enabled(lints, "shadowing", defaults.shadowing),
+                unreachable_pattern: enabled(lints,
+                                             "unreachablePattern",
+                                             defaults.unreachable_pattern),
+            };
+        }
+        result
+    }
+}
+
+/// The settings of the server, shared between every command
+#[derive(Clone)]
+pub struct SharedSettings(pub Arc<RwLock<Settings>>);
+
+impl SharedSettings {
+    pub fn new() -> SharedSettings {
+        SharedSettings(Arc::new(RwLock::new(Settings::default())))
+    }
+
+    pub fn get(&self) -> Settings {
+        self.0.read().unwrap().clone()
+    }
+
+    /// Replaces the current settings, returning the previous ones
+    pub fn set(&self, settings: Settings) -> Settings {
+        ::std::mem::replace(&mut *self.0.write().unwrap(), settings)
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+
+use serde_json::value::{Value, to_value};
+
+use gluon::base::ast::{Expr, Pattern, SpannedExpr, TypeBinding, ValueBinding};
+use gluon::base::pos::{BytePos, Span};
+use gluon::base::symbol::Symbol;
+
+use languageserver_types::SymbolKind;
+
+use position::{Lines, PositionEncoding};
+use query;
+
+/// A symbol in the outline of a document. `languageserver_types` does not yet know about the
+/// hierarchical `DocumentSymbol` so this is serialized by hand.
+pub struct DocumentSymbol {
+    pub name: String,
+    pub detail: Option<String>,
+    pub kind: SymbolKind,
+    pub span: Span<BytePos>,
+    pub selection_span: Span<BytePos>,
+    pub children: Vec<DocumentSymbol>,
+}
+
+impl DocumentSymbol {
+    /// Serializes the symbol with positions in `source`, whose lines are `lines`
+    pub fn to_value(&self, source: &str, lines: &Lines, encoding: PositionEncoding) -> Value {
+        let mut map = BTreeMap::new();
+        map.insert("name".to_string(), Value::String(self.name.clone()));
+        if let Some(ref detail) = self.detail {
+            map.insert("detail".to_string(), Value::String(detail.clone()));
+        }
+        map.insert("kind".to_string(), to_value(&self.kind));
+        map.insert("range".to_string(),
+                   to_value(&lines.range(source, self.span, encoding)));
+        map.insert("selectionRange".to_string(),
+                   to_value(&lines.range(source, self.selection_span, encoding)));
+        map.insert("children".to_string(),
+                   Value::Array(self.children
+                       .iter()
+                       .map(|child| child.to_value(source, lines, encoding))
+                       .collect()));
+        Value::Object(map)
+    }
+}
+
+/// Creates the outline of a module from its `let` and `type` bindings
+pub fn document_symbols(source: &str, expr: &SpannedExpr<Symbol>) -> Vec<DocumentSymbol> {
+    let mut symbols = Vec::new();
+    collect(source, expr, &mut symbols);
+    symbols
+}
+
+fn collect(source: &str, expr: &SpannedExpr<Symbol>, symbols: &mut Vec<DocumentSymbol>) {
+    if query::is_inserted(expr.span) {
+        match expr.value {
+            Expr::LetBindings(_, ref body) |
+            Expr::TypeBindings(_, ref body) => collect(source, body, symbols),
+            _ => (),
+        }
+        return;
+    }
+    match expr.value {
+        Expr::LetBindings(ref bindings, ref body) => {
+            for bind in bindings {
+                value_binding(source, bind, symbols);
+            }
+            collect(source, body, symbols);
+        }
+        Expr::TypeBindings(ref bindings, ref body) => {
+            let mut start = expr.span.start.to_usize();
+            for bind in bindings {
+                if let Some(symbol) = type_binding(source, bind, start, expr.span.end) {
+                    start = symbol.selection_span.end.to_usize();
+                    symbols.push(symbol);
+                }
+            }
+            collect(source, body, symbols);
+        }
+        Expr::Lambda(ref lambda) => collect(source, &lambda.body, symbols),
+        Expr::Record { ref exprs, .. } => {
+            let mut start = expr.span.start.to_usize();
+            for (i, &(ref field, ref value)) in exprs.iter().enumerate() {
+                let name = query::name(field);
+                let end = query::field_name_end(source, expr.span, exprs, i);
+                let value = value.as_ref().filter(|value| !query::is_punned(expr.span, value));
+                let found = match query::find_word(source, name, start, end.to_usize()) {
+                    Some(found) => found,
+                    None => continue,
+                };
+                let selection_span = Span::new(BytePos::from(found),
+                                               BytePos::from(found + name.len()));
+                let field_end = value.map_or(selection_span.end,
+                                             |value| query::full_span(source, value).end);
+                let mut children = Vec::new();
+                if let Some(value) = value {
+                    collect(source, value, &mut children);
+                }
+                symbols.push(DocumentSymbol {
+                    name: name.to_string(),
+                    detail: value.and_then(expr_type),
+                    kind: SymbolKind::Field,
+                    span: Span::new(selection_span.start, field_end),
+                    selection_span: selection_span,
+                    children: children,
+                });
+                start = field_end.to_usize();
+            }
+        }
+        _ => (),
+    }
+}
+
+fn value_binding(source: &str, bind: &ValueBinding<Symbol>, symbols: &mut Vec<DocumentSymbol>) {
+    let id = match bind.name.value {
+        Pattern::Ident(ref id) => id,
+        // Destructuring patterns do not define anything worth showing in the outline
+        _ => return,
+    };
+    let is_function = match bind.expr.value {
+        Expr::Lambda(_) => true,
+        _ => !bind.args.is_empty(),
+    };
+    let mut children = Vec::new();
+    collect(source, &bind.expr, &mut children);
+    symbols.push(DocumentSymbol {
+        name: query::name(&id.name).to_string(),
+        detail: Some(format!("{}", id.typ)),
+        kind: if is_function {
+            SymbolKind::Function
+        } else {
+            SymbolKind::Variable
+        },
+        span: Span::new(bind.name.span.start, query::full_span(source, &bind.expr).end),
+        selection_span: bind.name.span,
+        children: children,
+    });
+}
+
+fn type_binding(source: &str,
+                bind: &TypeBinding<Symbol>,
+                start: usize,
+                end: BytePos)
+                -> Option<DocumentSymbol> {
+    let name = query::name(&bind.name);
+    query::find_word(source, name, start, end.to_usize()).map(|found| {
+        let selection_span = Span::new(BytePos::from(found), BytePos::from(found + name.len()));
+        DocumentSymbol {
+            name: name.to_string(),
+            detail: Some(format!("{}", bind.alias.typ)),
+            kind: SymbolKind::Class,
+            span: selection_span,
+            selection_span: selection_span,
+            children: Vec::new(),
+        }
+    })
+}
+
+fn expr_type(expr: &SpannedExpr<Symbol>) -> Option<String> {
+    match expr.value {
+        Expr::Ident(ref id) => Some(format!("{}", id.typ)),
+        Expr::Lambda(ref lambda) => Some(format!("{}", lambda.id.typ)),
+        Expr::Record { ref typ, .. } => Some(format!("{}", typ)),
+        _ => None,
+    }
+}
+
+/// Returns every symbol in `symbols` and their children along with the symbol containing them
+pub fn flatten(symbols: &[DocumentSymbol]) -> Vec<(&DocumentSymbol, Option<&DocumentSymbol>)> {
+    let mut flattened = Vec::new();
+    let mut stack: Vec<_> = symbols.iter().rev().map(|symbol| (symbol, None)).collect();
+    while let Some((symbol, container)) = stack.pop() {
+        flattened.push((symbol, container));
+        stack.extend(symbol.children.iter().rev().map(|child| (child, Some(symbol))));
+    }
+    flattened
+}
+
+/// Matches `query` against `name` if every character of `query` appears in `name` in order,
+/// ignoring case. Higher scores are given to matches with consecutive characters and to matches
+/// at the start of `name`.
+pub fn fuzzy_match(query: &str, name: &str) -> Option<i32> {
+    let mut score = 0;
+    let mut name_chars = name.chars().flat_map(char::to_lowercase).enumerate();
+    let mut previous = None;
+    for query_char in query.chars().flat_map(char::to_lowercase) {
+        let found = name_chars.by_ref().find(|&(_, c)| c == query_char);
+        match found {
+            Some((i, _)) => {
+                if i == 0 {
+                    score += 3;
+                }
+                if previous.map_or(false, |previous| previous + 1 == i) {
+                    score += 2;
+                }
+                score += 1;
+                previous = Some(i);
+            }
+            None => return None,
+        }
+    }
+    // Prefer shorter names when the query matches equally well
+    Some(score * 100 - name.len() as i32)
+}
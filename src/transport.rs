@@ -0,0 +1,40 @@
+use std::io::{self, BufReader};
+use std::net::{Shutdown, TcpListener, TcpStream};
+
+use config::Config;
+
+/// Serves a single client over `stream` until it exits or disconnects
+fn serve_tcp(config: &Config, stream: TcpStream) -> io::Result<()> {
+    let input = BufReader::new(try!(stream.try_clone()));
+    let output = try!(stream.try_clone());
+    ::serve(config, input, output);
+    // Stop the reader thread if the client sent `exit` but kept the connection open
+    match stream.shutdown(Shutdown::Both) {
+        Err(ref err) if err.kind() == io::ErrorKind::NotConnected => Ok(()),
+        result => result,
+    }
+}
+
+/// Accepts clients on `addr`, serving them one at a time
+pub fn listen(config: &Config, addr: &str) -> io::Result<()> {
+    let listener = try!(TcpListener::bind(addr));
+    info!("Listening on {}", try!(listener.local_addr()));
+    for stream in listener.incoming() {
+        let stream = try!(stream);
+        let peer = stream.peer_addr()
+            .map(|addr| addr.to_string())
+            .unwrap_or_else(|_| "unknown".to_string());
+        info!("Client {} connected", peer);
+        if let Err(err) = serve_tcp(config, stream) {
+            error!("Error serving {}: {}", peer, err);
+        }
+        info!("Client {} disconnected", peer);
+    }
+    Ok(())
+}
+
+/// Connects to a client listening on `addr`
+pub fn connect(config: &Config, addr: &str) -> io::Result<()> {
+    let stream = try!(TcpStream::connect(addr));
+    serve_tcp(config, stream)
+}
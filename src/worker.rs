@@ -0,0 +1,304 @@
+use std::cmp;
+use std::collections::HashSet;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use gluon::{RootedThread, filename_to_module};
+use gluon::base::fnv::FnvMap;
+use gluon::import::Import;
+use gluon::vm::thread::Thread;
+
+use CheckImporter;
+use cancellation::CancellationToken;
+use document::DocumentStore;
+use output::Output;
+use position::SharedEncoding;
+use settings::SharedSettings;
+use workspace::Workspaces;
+
+/// How long the worker waits for more edits before it starts checking
+const DEBOUNCE: u64 = 200;
+
+/// How often a request which waits for documents to be checked looks for its cancellation
+const CANCEL_POLL: u64 = 50;
+
+/// How many seconds a request waits for pending checks before it is answered with whatever has
+/// been checked so far
+const WAIT_TIMEOUT: u64 = 30;
+
+enum Task {
+    /// Check the text as the contents of the document
+    Check(String),
+    /// The document was closed and the text is its contents on disk. It is checked without
+    /// publishing diagnostics as the client no longer shows it.
+    Closed(String),
+    /// The module was removed so only its dependents need to be checked
+    Removed,
+    /// Index the workspace at the path into the vm
+    Index(RootedThread, PathBuf),
+}
+
+struct Job {
+    uri: String,
+    task: Task,
+    token: CancellationToken,
+    /// Whether the open documents which depend on the module are checked afterwards. Dependents
+    /// are found transitively so the checks of the dependents do not check their own dependents.
+    check_dependents: bool,
+}
+
+impl Job {
+    fn is_index(&self) -> bool {
+        match self.task {
+            Task::Index(..) => true,
+            _ => false,
+        }
+    }
+}
+
+struct State {
+    /// The token of the latest job submitted for each uri
+    tokens: FnvMap<String, CancellationToken>,
+    /// Documents which have been submitted but not checked yet
+    pending: HashSet<String>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    checked: Condvar,
+}
+
+impl Shared {
+    /// Locks the state. A panic while it was locked can not leave it inconsistent so the lock is
+    /// used even if it is poisoned.
+    fn state(&self) -> MutexGuard<State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Creates a job for `uri`, cancelling the previous job for it
+    fn job(&self, uri: &str, task: Task, check_dependents: bool) -> Job {
+        let token = CancellationToken::new();
+        if let Some(previous) = self.state().tokens.insert(uri.into(), token.clone()) {
+            previous.cancel();
+        }
+        Job {
+            uri: uri.into(),
+            task: task,
+            token: token,
+            check_dependents: check_dependents,
+        }
+    }
+
+    fn finished(&self, job: &Job) {
+        if job.token.is_cancelled() {
+            return;
+        }
+        let mut state = self.state();
+        state.pending.remove(&job.uri);
+        self.checked.notify_all();
+    }
+}
+
+/// Typechecks documents on a background thread and publishes their diagnostics when done. Open
+/// documents which depend on a checked document are checked again afterwards. Workspace folders
+/// are indexed on the same thread, after any document which is waiting to be checked, so that a
+/// large folder does not hold up the editor.
+///
+/// Queries are served from whatever the last completed check stored in the `CheckImporter`.
+/// Requests wait for the pending checks to finish first (see `wait_for_checks`) so that positions
+/// in a document which was just edited refer to the text that was checked. Submitting a document
+/// cancels any check of an older version of it that has not finished yet.
+///
+/// The background thread stops once every handle to the worker has been dropped.
+#[derive(Clone)]
+pub struct CheckWorker {
+    sender: Arc<Mutex<mpsc::Sender<Job>>>,
+    shared: Arc<Shared>,
+}
+
+impl CheckWorker {
+    pub fn spawn(workspaces: Workspaces,
+                 documents: DocumentStore,
+                 settings: SharedSettings,
+                 encoding: SharedEncoding,
+                 output: Output)
+                 -> CheckWorker {
+        let (sender, receiver) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                tokens: FnvMap::default(),
+                pending: HashSet::new(),
+            }),
+            checked: Condvar::new(),
+        });
+        let worker = CheckWorker {
+            sender: Arc::new(Mutex::new(sender)),
+            shared: shared.clone(),
+        };
+        thread::spawn(move || {
+            // Checks of open documents which depend on a module which was just checked. They are
+            // debounced like any other job so that they can be replaced by newer edits.
+            let mut dependents = Vec::new();
+            loop {
+                let mut jobs = if dependents.is_empty() {
+                    match receiver.recv() {
+                        Ok(job) => vec![job],
+                        Err(_) => break,
+                    }
+                } else {
+                    mem::replace(&mut dependents, Vec::new())
+                };
+                // Only check the latest version of each document once the edits stop coming in
+                loop {
+                    match receiver.recv_timeout(Duration::from_millis(DEBOUNCE)) {
+                        Ok(job) => {
+                            match jobs.iter().position(|previous| previous.uri == job.uri) {
+                                Some(i) => jobs[i] = job,
+                                None => jobs.push(job),
+                            }
+                        }
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                // Requests wait for the documents but not for the index
+                jobs.sort_by_key(Job::is_index);
+                for job in jobs {
+                    // A bug in the checker must not take down the worker as requests wait for it
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        if job.token.is_cancelled() {
+                            return;
+                        }
+                        let thread = workspaces.thread(&job.uri);
+                        // Indexing the workspace does not change any module the user is editing
+                        let changed = match job.task {
+                            Task::Check(ref text) => {
+                                ::run_diagnostics(&output,
+                                                  &thread,
+                                                  &settings.get(),
+                                                  encoding.get(),
+                                                  &job.token,
+                                                  &job.uri,
+                                                  text);
+                                true
+                            }
+                            Task::Closed(ref text) => {
+                                ::typecheck(&thread, &job.token, &job.uri, text);
+                                true
+                            }
+                            Task::Removed => true,
+                            Task::Index(ref thread, ref path) => {
+                                ::index_workspace(thread, &job.token, path);
+                                false
+                            }
+                        };
+                        if changed && job.check_dependents {
+                            // A document which is already queued moves to the end so that it
+                            // still comes after every module it imports
+                            for (uri, text) in open_dependents(&thread, &documents, &job.uri) {
+                                dependents.retain(|dependent: &Job| dependent.uri != uri);
+                                dependents.push(shared.job(&uri, Task::Check(text), false));
+                            }
+                        }
+                    }));
+                    if result.is_err() {
+                        error!("Checking `{}` panicked", job.uri);
+                    }
+                    shared.finished(&job);
+                }
+            }
+        });
+        worker
+    }
+
+    /// Schedules `text` to be checked as the contents of `uri`
+    pub fn submit(&self, uri: &str, text: String) {
+        self.shared.state().pending.insert(uri.into());
+        self.send(uri, Task::Check(text));
+    }
+
+    /// Schedules the closed document at `uri` to be checked with `text`, its contents on disk
+    pub fn submit_closed(&self, uri: &str, text: String) {
+        self.shared.state().pending.insert(uri.into());
+        self.send(uri, Task::Closed(text));
+    }
+
+    /// Schedules the modules depending on the (removed) module at `uri` to be checked
+    pub fn submit_removed(&self, uri: &str) {
+        self.send(uri, Task::Removed);
+    }
+
+    /// Schedules the workspace folder at `uri` to be indexed into `thread`, replacing any index of
+    /// it which has not finished yet
+    pub fn submit_index(&self, uri: &str, thread: RootedThread, path: PathBuf) {
+        self.send(uri, Task::Index(thread, path));
+    }
+
+    fn send(&self, uri: &str, task: Task) {
+        let job = self.shared.job(uri, task, true);
+        if self.sender.lock().unwrap().send(job).is_err() {
+            error!("The check worker has stopped");
+        }
+    }
+
+    /// Cancels any pending check of `uri`
+    pub fn cancel(&self, uri: &str) {
+        let mut state = self.shared.state();
+        if let Some(token) = state.tokens.remove(uri) {
+            token.cancel();
+        }
+        state.pending.remove(uri);
+        self.shared.checked.notify_all();
+    }
+
+    /// Blocks until every submitted document has been checked. Returns early if `token` is
+    /// cancelled or if the checks take longer than `WAIT_TIMEOUT`, in which case later requests no
+    /// longer wait for them.
+    pub fn wait_for_checks(&self, token: &CancellationToken) {
+        let deadline = Instant::now() + Duration::from_secs(WAIT_TIMEOUT);
+        let mut state = self.shared.state();
+        while !state.pending.is_empty() && !token.is_cancelled() {
+            let now = Instant::now();
+            if now >= deadline {
+                error!("Gave up waiting for {} documents to be checked", state.pending.len());
+                state.pending.clear();
+                return;
+            }
+            let timeout = cmp::min(deadline - now, Duration::from_millis(CANCEL_POLL));
+            state = match self.shared.checked.wait_timeout(state, timeout) {
+                Ok((state, _)) => state,
+                Err(err) => err.into_inner().0,
+            };
+        }
+    }
+}
+
+/// Returns the uri and text of every open document which imports the module at `uri`, directly
+/// or indirectly, ordered so that each document comes after the documents it imports
+fn open_dependents(thread: &Thread, documents: &DocumentStore, uri: &str) -> Vec<(String, String)> {
+    let module = filename_to_module(&::strip_file_prefix(thread, uri));
+    let dependents = {
+        let import = thread.get_macros().get("import").expect("Import macro");
+        let import = import.downcast_ref::<Import<CheckImporter>>().expect("Check importer");
+        let graph = import.importer.1.lock().unwrap();
+        graph.dependents(&module)
+    };
+    if dependents.is_empty() {
+        return vec![];
+    }
+    let open = documents.documents();
+    dependents.iter()
+        .filter_map(|dependent| {
+            open.iter().find(|&&(ref uri, _)| {
+                filename_to_module(&::strip_file_prefix(thread, uri)) == *dependent
+            })
+        })
+        .filter(|&&(ref uri, _)| !documents.is_foreign(uri))
+        .map(|&(ref uri, ref document)| (uri.clone(), document.text.clone()))
+        .collect()
+}
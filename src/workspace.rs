@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use gluon::RootedThread;
+
+use url::Url;
+
+/// A workspace folder. Every folder has a vm of its own so that its import paths and modules are
+/// kept apart from those of the other folders.
+struct Folder {
+    uri: String,
+    path: PathBuf,
+    thread: RootedThread,
+}
+
+struct State {
+    /// The vm used for documents outside of every folder
+    default: RootedThread,
+    folders: Vec<Folder>,
+    /// Import paths from the command line
+    import_paths: Vec<PathBuf>,
+    /// Import paths from the client's settings
+    settings_paths: Vec<PathBuf>,
+}
+
+/// The folders of the workspace and the vm used for each of them
+#[derive(Clone)]
+pub struct Workspaces(Arc<RwLock<State>>);
+
+impl Workspaces {
+    pub fn new(import_paths: Vec<PathBuf>) -> Workspaces {
+        Workspaces(Arc::new(RwLock::new(State {
+            default: ::new_check_vm(import_paths.clone()),
+            folders: Vec::new(),
+            import_paths: import_paths,
+            settings_paths: Vec::new(),
+        })))
+    }
+
+    /// Adds the folder at `uri`. Returns the vm of the folder and its path so that the folder can
+    /// be indexed, or `None` if `uri` is not a directory or has already been added.
+    pub fn add_folder(&self, uri: &str) -> Option<(RootedThread, PathBuf)> {
+        let path = match file_path(uri) {
+            Some(ref path) if path.is_dir() => canonicalize(path),
+            _ => return None,
+        };
+        let mut state = self.0.write().unwrap();
+        if state.folders.iter().any(|folder| folder.path == path) {
+            return None;
+        }
+        let thread = ::new_check_vm(state.folder_import_paths(&path));
+        state.folders.push(Folder {
+            uri: uri.into(),
+            path: path.clone(),
+            thread: thread.clone(),
+        });
+        Some((thread, path))
+    }
+
+    pub fn remove_folder(&self, uri: &str) {
+        let path = file_path(uri).map(|path| canonicalize(&path));
+        self.0
+            .write()
+            .unwrap()
+            .folders
+            .retain(|folder| folder.uri != uri && Some(&folder.path) != path.as_ref());
+    }
+
+    /// Returns the vm of the innermost folder containing `uri`
+    pub fn thread(&self, uri: &str) -> RootedThread {
+        let state = self.0.read().unwrap();
+        file_path(uri)
+            .and_then(|path| {
+                let path = canonicalize(&path);
+                state.folders
+                    .iter()
+                    .filter(|folder| path.starts_with(&folder.path))
+                    .max_by_key(|folder| folder.path.components().count())
+            })
+            .map(|folder| folder.thread.clone())
+            .unwrap_or_else(|| state.default.clone())
+    }
+
+    /// Returns the vms of every folder as well as the default vm
+    pub fn threads(&self) -> Vec<RootedThread> {
+        let state = self.0.read().unwrap();
+        Some(state.default.clone())
+            .into_iter()
+            .chain(state.folders.iter().map(|folder| folder.thread.clone()))
+            .collect()
+    }
+
+    /// Replaces the import paths which came from the client's settings. Modules which are already
+    /// loaded may have been found through the old paths so every vm is replaced by an empty one.
+    /// Returns the uri, vm and path of every folder so that the folders can be indexed again.
+    pub fn set_settings_paths(&self,
+                              settings_paths: Vec<PathBuf>)
+                              -> Vec<(String, RootedThread, PathBuf)> {
+        let mut state = self.0.write().unwrap();
+        state.settings_paths = settings_paths;
+
+        let mut import_paths = state.import_paths.clone();
+        import_paths.extend(state.settings_paths.iter().cloned());
+        state.default = ::new_check_vm(import_paths);
+
+        let threads: Vec<_> = state.folders
+            .iter()
+            .map(|folder| ::new_check_vm(state.folder_import_paths(&folder.path)))
+            .collect();
+        state.folders
+            .iter_mut()
+            .zip(threads)
+            .map(|(folder, thread)| {
+                folder.thread = thread;
+                (folder.uri.clone(), folder.thread.clone(), folder.path.clone())
+            })
+            .collect()
+    }
+}
+
+impl State {
+    /// The import paths of the folder at `path`, which is searched before any other path
+    fn folder_import_paths(&self, path: &Path) -> Vec<PathBuf> {
+        let mut import_paths = vec![path.to_path_buf()];
+        import_paths.extend(self.import_paths.iter().cloned());
+        import_paths.extend(self.settings_paths.iter().cloned());
+        import_paths
+    }
+}
+
+fn file_path(uri: &str) -> Option<PathBuf> {
+    Url::parse(uri).ok().and_then(|url| url.to_file_path().ok())
+}
+
+fn canonicalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,87 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+
+mod support;
+
+use languageserver_types::{Position, TextDocumentIdentifier, TextDocumentPositionParams};
+
+use gluon_language_server::cancellation::{Cancellation, CancellationToken, REQUEST_CANCELLED};
+
+#[test]
+fn token() {
+    let token = CancellationToken::new();
+    let clone = token.clone();
+    assert!(!token.is_cancelled());
+    clone.cancel();
+    assert!(token.is_cancelled());
+}
+
+#[test]
+fn cancel_pending_request() {
+    let cancellation = Cancellation::new();
+    cancellation.register("1");
+    cancellation.register("2");
+    cancellation.cancel("1");
+
+    assert!(cancellation.start(Some("1")).is_cancelled());
+    assert!(cancellation.current().is_cancelled());
+    cancellation.finish(Some("1"));
+    assert!(!cancellation.current().is_cancelled());
+
+    assert!(!cancellation.start(Some("2")).is_cancelled());
+}
+
+#[test]
+fn cancel_current_request() {
+    let cancellation = Cancellation::new();
+    cancellation.register("1");
+    let token = cancellation.start(Some("1"));
+    cancellation.cancel("1");
+    assert!(token.is_cancelled());
+    assert!(cancellation.current().is_cancelled());
+}
+
+#[test]
+fn finished_and_unknown_requests_are_not_cancelled() {
+    let cancellation = Cancellation::new();
+    cancellation.register("1");
+    cancellation.start(Some("1"));
+    cancellation.finish(Some("1"));
+    cancellation.cancel("1");
+    cancellation.cancel("2");
+
+    assert!(!cancellation.start(Some("1")).is_cancelled());
+    assert!(!cancellation.start(Some("2")).is_cancelled());
+    assert!(!cancellation.start(None).is_cancelled());
+}
+
+#[test]
+fn cancel_request() {
+    let error = support::send_rpc_error(|mut stdin| {
+        support::did_open(stdin, "test", "let x = 1\nx");
+
+        // The request is queued behind the check of `test` so the cancellation arrives before it
+        // is handled
+        let hover = support::method_call("textDocument/hover",
+                                         2,
+                                         TextDocumentPositionParams {
+                                             text_document: TextDocumentIdentifier {
+                                                 uri: "test".into(),
+                                             },
+                                             position: Position {
+                                                 line: 1,
+                                                 character: 0,
+                                             },
+                                         });
+        support::write_message(&mut stdin, hover).unwrap();
+        let params: serde_json::Value = serde_json::from_str(r#"{ "id": 2 }"#).unwrap();
+        let cancel = support::notification("$/cancelRequest", params);
+        support::write_message(stdin, cancel).unwrap();
+    });
+
+    assert_eq!(error.code.code(), REQUEST_CANCELLED);
+}
@@ -0,0 +1,244 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+
+mod support;
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::str;
+
+use serde_json::Value;
+
+fn write_file(name: &str, contents: &str) -> PathBuf {
+    support::write_file(&support::temp_dir("check"), name, contents)
+}
+
+fn check(args: &[&str], path: &Path) -> Output {
+    Command::new(support::server_path())
+        .arg("check")
+        .args(args)
+        .arg(path)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn no_errors() {
+    let path = write_file("gluon_language_server_check_ok.glu", "let x = 1\nx\n");
+    let output = check(&[], &path);
+    assert!(output.status.success());
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.contains("found 0 errors"), "{}", stdout);
+}
+
+#[test]
+fn type_error() {
+    let path = write_file("gluon_language_server_check_error.glu", "let x = 1\nx #Int+ \"\"\n");
+    let output = check(&[], &path);
+    assert!(!output.status.success());
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.starts_with("error: "), "{}", stdout);
+    assert!(stdout.contains(&format!("  --> {}:", path.display())), "{}", stdout);
+}
+
+#[test]
+fn json() {
+    let path = write_file("gluon_language_server_check_json.glu", "let x = 1\ny\n");
+    let output = check(&["--json"], &path);
+    assert!(!output.status.success());
+    let value: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let files = value.as_array().expect("array");
+    assert_eq!(files.len(), 1);
+    let diagnostics = files[0].find("diagnostics").and_then(|d| d.as_array()).expect("diagnostics");
+    assert_eq!(diagnostics.len(), 1);
+    let message = diagnostics[0].find("message").and_then(|message| message.as_str());
+    assert!(message.map_or(false, |message| message.contains("y")), "{:?}", message);
+}
+
+#[test]
+fn json_code_and_related_information() {
+    let path = write_file("gluon_language_server_check_related.glu",
+                          "let f x : Int -> Int = x\nf \"\"\n");
+    let output = check(&["--json"], &path);
+    assert!(!output.status.success());
+    let value: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let diagnostics = value.as_array()
+        .and_then(|files| files[0].find("diagnostics"))
+        .and_then(|d| d.as_array())
+        .expect("diagnostics");
+    assert_eq!(diagnostics.len(), 1);
+    let diagnostic = &diagnostics[0];
+    assert_eq!(diagnostic.find("code").and_then(|code| code.as_str()),
+               Some("type-mismatch"));
+    assert_eq!(diagnostic.find("source").and_then(|source| source.as_str()),
+               Some("gluon"));
+
+    let related = diagnostic.find("relatedInformation")
+        .and_then(|related| related.as_array())
+        .expect("relatedInformation");
+    assert_eq!(related.len(), 1);
+    let start = related[0].find_path(&["location", "range", "start"]).expect("start");
+    assert_eq!(start.find("line").and_then(|line| line.as_u64()), Some(0));
+    assert_eq!(start.find("character").and_then(|character| character.as_u64()),
+               Some(4));
+}
+
+/// Returns the start (line and character) of the related information of the only diagnostic of
+/// the file at `path`
+fn related_start(path: &Path) -> (Option<u64>, Option<u64>) {
+    let output = check(&["--json"], path);
+    assert!(!output.status.success());
+    let value: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let diagnostics = value.as_array()
+        .and_then(|files| files[0].find("diagnostics"))
+        .and_then(|d| d.as_array())
+        .expect("diagnostics");
+    assert_eq!(diagnostics.len(), 1);
+    let related = diagnostics[0].find("relatedInformation")
+        .and_then(|related| related.as_array())
+        .expect("relatedInformation");
+    let start = related[0].find_path(&["location", "range", "start"]).expect("start");
+    (start.find("line").and_then(|line| line.as_u64()),
+     start.find("character").and_then(|character| character.as_u64()))
+}
+
+#[test]
+fn json_related_information_of_annotated_binding() {
+    let path = write_file("gluon_language_server_check_annotation.glu",
+                          "let y = 1\nlet x : Int = \"\"\nx\n");
+    assert_eq!(related_start(&path), (Some(1), Some(4)));
+}
+
+#[test]
+fn json_related_information_of_record_field() {
+    let path = write_file("gluon_language_server_check_record_field.glu",
+                          "let r : { a : Int, b : Int } = { a = 1, b = \"\" }\nr\n");
+    assert_eq!(related_start(&path), (Some(0), Some(4)));
+}
+
+#[test]
+fn json_macro_and_type_errors() {
+    let path = write_file("gluon_language_server_check_macro_and_type.glu",
+                          "let m = import \"gluon_language_server_missing\"\n1 #Int+ \"\"\n");
+    let output = check(&["--json"], &path);
+    assert!(!output.status.success());
+    let value: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let diagnostics = value.as_array()
+        .and_then(|files| files[0].find("diagnostics"))
+        .and_then(|d| d.as_array())
+        .expect("diagnostics");
+    let codes: Vec<_> = diagnostics.iter()
+        .filter_map(|diagnostic| diagnostic.find("code").and_then(|code| code.as_str()))
+        .collect();
+    assert_eq!(codes, ["macro-error", "type-mismatch"]);
+}
+
+#[test]
+fn json_multiple_failing_imports() {
+    let path = write_file("gluon_language_server_check_imports.glu",
+                          "let a = import \"gluon_language_server_missing_a\"\nlet b = import \
+                           \"gluon_language_server_missing_b\"\n1\n");
+    let output = check(&["--json"], &path);
+    assert!(!output.status.success());
+    let value: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let diagnostics = value.as_array()
+        .and_then(|files| files[0].find("diagnostics"))
+        .and_then(|d| d.as_array())
+        .expect("diagnostics");
+    let codes: Vec<_> = diagnostics.iter()
+        .filter_map(|diagnostic| diagnostic.find("code").and_then(|code| code.as_str()))
+        .collect();
+    assert_eq!(codes, ["macro-error", "macro-error"]);
+    let messages: Vec<_> = diagnostics.iter()
+        .filter_map(|diagnostic| diagnostic.find("message").and_then(|message| message.as_str()))
+        .collect();
+    assert!(messages[0].contains("missing_a"), "{:?}", messages);
+    assert!(messages[1].contains("missing_b"), "{:?}", messages);
+}
+
+#[test]
+fn json_warnings() {
+    let path = write_file("gluon_language_server_check_warnings.glu",
+                          r#"let prelude = import "std/prelude.glu"
+type Choice = | A Int | B
+let unused = 1
+let x = 1
+match A x with
+| A a -> a
+| B -> 0
+| A b -> b
+"#);
+    let output = check(&["--json"], &path);
+    // Warnings do not fail the check
+    assert!(output.status.success());
+    let value: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let diagnostics = value.as_array()
+        .and_then(|files| files[0].find("diagnostics"))
+        .and_then(|d| d.as_array())
+        .expect("diagnostics");
+    let warnings: Vec<_> = diagnostics.iter()
+        .map(|diagnostic| {
+            (diagnostic.find("code").and_then(|code| code.as_str()),
+             diagnostic.find_path(&["range", "start", "line"]).and_then(|line| line.as_u64()),
+             diagnostic.find("severity").and_then(|severity| severity.as_u64()))
+        })
+        .collect();
+    assert_eq!(warnings,
+               vec![(Some("unused-import"), Some(0), Some(2)),
+                    (Some("unused-binding"), Some(2), Some(2)),
+                    (Some("unreachable-pattern"), Some(7), Some(2))]);
+}
+
+#[test]
+fn bindings_used_in_records_are_not_reported() {
+    let path = write_file("gluon_language_server_check_record_uses.glu",
+                          "let x = 1\nlet y = 2\n{ z = y, x }\n");
+    let output = check(&[], &path);
+    assert!(output.status.success());
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.contains("found 0 errors and 0 warnings"), "{}", stdout);
+}
+
+#[test]
+fn unreadable_file_does_not_stop_the_check() {
+    let dir = support::temp_dir("check_unreadable");
+    support::write_file(&dir, "ok.glu", "let x = 1\nx\n");
+    let missing = dir.join("missing.glu");
+    let output = Command::new(support::server_path())
+        .arg("check")
+        .arg(&missing)
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.contains(&format!("  --> {}:1:1", missing.display())), "{}", stdout);
+    assert!(stdout.contains("Checked 2 files, found 1 error and 0 warnings"), "{}", stdout);
+}
+
+#[test]
+fn import_errors_point_at_the_imported_file() {
+    let dir = support::temp_dir("check_import");
+    let imported = support::write_file(&dir, "b.glu", "let x : Int = \"\"\n{ x }\n");
+    let path = support::write_file(&dir, "a.glu", "let b = import \"b.glu\"\nb.x\n");
+    let output = Command::new(support::server_path())
+        .arg("check")
+        .arg("--import-path")
+        .arg(&dir)
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.contains(&format!("  --> {}:1:9", path.display())), "{}", stdout);
+    assert!(stdout.contains("let b = import \"b.glu\"\n  |         ^^^^^^^^^^^^^^"),
+            "{}",
+            stdout);
+    assert!(stdout.contains("  = code: import-error"), "{}", stdout);
+    assert!(stdout.contains(&format!("  --> {}:1:5", imported.display())), "{}", stdout);
+    assert!(stdout.contains("Checked 1 file, found 2 errors and 0 warnings"), "{}", stdout);
+}
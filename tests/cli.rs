@@ -0,0 +1,35 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+
+mod support;
+
+use std::process::Command;
+use std::str;
+
+#[test]
+fn version() {
+    let output = Command::new(support::server_path()).arg("--version").output().unwrap();
+    assert!(output.status.success());
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.starts_with("gluon_language-server "), "{}", stdout);
+}
+
+#[test]
+fn help() {
+    let output = Command::new(support::server_path()).arg("--help").output().unwrap();
+    assert!(output.status.success());
+    let stdout = str::from_utf8(&output.stdout).unwrap();
+    assert!(stdout.contains("--import-path"), "{}", stdout);
+}
+
+#[test]
+fn unknown_argument() {
+    let output = Command::new(support::server_path()).arg("--bogus").output().unwrap();
+    assert!(!output.status.success());
+    let stderr = str::from_utf8(&output.stderr).unwrap();
+    assert!(stderr.contains("--bogus"), "{}", stderr);
+}
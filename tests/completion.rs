@@ -10,8 +10,8 @@ mod support;
 
 use std::io::Write;
 
-use languageserver_types::{CompletionItem, CompletionItemKind, Position, TextDocumentIdentifier,
-                           TextDocumentPositionParams};
+use languageserver_types::{CompletionItem, CompletionItemKind, Position, Range,
+                           TextDocumentIdentifier, TextDocumentPositionParams};
 
 fn completion<W: ?Sized>(stdin: &mut W, id: u64, uri: &str, position: Position)
     where W: Write,
@@ -80,3 +80,39 @@ fn prelude_completion() {
                     ..CompletionItem::default()
                 }]);
 }
+
+#[test]
+fn completion_on_edited_line() {
+    let completions: Vec<CompletionItem> = support::send_rpc(|mut stdin| {
+        let text = r#"
+let test = 2
+let test1 = ""
+1
+"#;
+        support::did_open(stdin, "test", text);
+        support::did_change(stdin,
+                            "test",
+                            2,
+                            Range {
+                                start: Position {
+                                    line: 3,
+                                    character: 0,
+                                },
+                                end: Position {
+                                    line: 3,
+                                    character: 1,
+                                },
+                            },
+                            "tes");
+
+        completion(stdin,
+                   1,
+                   "test",
+                   Position {
+                       line: 3,
+                       character: 3,
+                   })
+    });
+    assert_eq!(completions.iter().map(|item| &item.label[..]).collect::<Vec<_>>(),
+               ["test", "test1"]);
+}
@@ -0,0 +1,163 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+extern crate url;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+
+mod support;
+
+use std::io::Write;
+use std::path::Path;
+
+use languageserver_types::{Location, Position, Range, TextDocumentPositionParams,
+                           TextDocumentIdentifier};
+
+fn definition<W: ?Sized>(stdin: &mut W, id: u64, uri: &str, position: Position)
+    where W: Write,
+{
+    let definition = support::method_call("textDocument/definition",
+                                          id,
+                                          TextDocumentPositionParams {
+                                              text_document: TextDocumentIdentifier {
+                                                  uri: uri.into(),
+                                              },
+                                              position: position,
+                                          });
+
+    support::write_message(stdin, definition).unwrap();
+}
+
+fn uri(path: &Path) -> String {
+    url::Url::from_file_path(path).unwrap().to_string()
+}
+
+/// Jumps to the definition at `position` in a `main.glu` containing `main`, next to an `other.glu`
+/// which exports `x`
+fn imported_definition(name: &str, main: &str, position: Position) -> Location {
+    let dir = support::temp_dir(name);
+    support::write_file(&dir, "other.glu", "let x = 1\n{ x }\n");
+    let main_path = support::write_file(&dir, "main.glu", main);
+
+    let location: Location = support::send_rpc(|mut stdin| {
+        support::initialize(stdin, 1, &[uri(&dir)]);
+        support::did_open(stdin, &uri(&main_path), main);
+
+        definition(stdin, 2, &uri(&main_path), position);
+    });
+    assert_eq!(location.uri, uri(&dir.join("other.glu")));
+    location
+}
+
+#[test]
+fn let_binding() {
+    let location: Location = support::send_rpc(|mut stdin| {
+        let src = r#"
+let test = 1
+test
+"#;
+        support::did_open(stdin, "test", src);
+
+        definition(stdin,
+                   2,
+                   "test",
+                   Position {
+                       line: 2,
+                       character: 2,
+                   });
+    });
+
+    assert_eq!(location,
+               Location {
+                   uri: "test".into(),
+                   range: Range {
+                       start: Position {
+                           line: 1,
+                           character: 4,
+                       },
+                       end: Position {
+                           line: 1,
+                           character: 8,
+                       },
+                   },
+               });
+}
+
+#[test]
+fn function_argument() {
+    let location: Location = support::send_rpc(|mut stdin| {
+        let src = r#"
+let f x = x
+f 1
+"#;
+        support::did_open(stdin, "test", src);
+
+        definition(stdin,
+                   2,
+                   "test",
+                   Position {
+                       line: 1,
+                       character: 10,
+                   });
+    });
+
+    assert_eq!(location,
+               Location {
+                   uri: "test".into(),
+                   range: Range {
+                       start: Position {
+                           line: 1,
+                           character: 6,
+                       },
+                       end: Position {
+                           line: 1,
+                           character: 7,
+                       },
+                   },
+               });
+}
+
+#[test]
+fn destructured_import() {
+    let location = imported_definition("definition_destructured",
+                                       "let { x } = import \"other.glu\"\nx\n",
+                                       Position {
+                                           line: 1,
+                                           character: 0,
+                                       });
+
+    assert_eq!(location.range,
+               Range {
+                   start: Position {
+                       line: 0,
+                       character: 4,
+                   },
+                   end: Position {
+                       line: 0,
+                       character: 5,
+                   },
+               });
+}
+
+#[test]
+fn projection_of_import() {
+    let location = imported_definition("definition_projection",
+                                       "let m = import \"other.glu\"\nm.x\n",
+                                       Position {
+                                           line: 1,
+                                           character: 2,
+                                       });
+
+    assert_eq!(location.range,
+               Range {
+                   start: Position {
+                       line: 0,
+                       character: 4,
+                   },
+                   end: Position {
+                       line: 0,
+                       character: 5,
+                   },
+               });
+}
@@ -0,0 +1,89 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+extern crate url;
+
+mod support;
+
+use std::path::Path;
+
+use languageserver_types::{Position, Range};
+
+fn uri(path: &Path) -> String {
+    url::Url::from_file_path(path).unwrap().to_string()
+}
+
+/// Replaces the whole first line of the document at `uri`, which is `line`
+fn replace_first_line(server: &mut support::Server, uri: &str, line: &str, text: &str) {
+    support::did_change(server.stdin(),
+                        uri,
+                        2,
+                        Range {
+                            start: Position {
+                                line: 0,
+                                character: 0,
+                            },
+                            end: Position {
+                                line: 0,
+                                character: line.len() as u64,
+                            },
+                        },
+                        text);
+}
+
+/// Opens the file `name` in `dir` with its contents on disk, `text`
+fn open(server: &mut support::Server, dir: &Path, name: &str, text: &str) -> String {
+    let uri = uri(&support::write_file(dir, name, text));
+    support::did_open(server.stdin(), &uri, text);
+    uri
+}
+
+#[test]
+fn dependents_are_checked_after_their_imports() {
+    let dir = support::temp_dir("dependents_chain");
+    let mut server = support::Server::spawn();
+    support::initialize(server.stdin(), 1, &[uri(&dir)]);
+    server.wait_for_response(1);
+
+    let c = open(&mut server, &dir, "c.glu", "{ x = 1 }\n");
+    server.wait_for_diagnostics(&c, |diagnostics| diagnostics.is_empty());
+    let b = open(&mut server,
+                 &dir,
+                 "b.glu",
+                 "let c = import \"c.glu\"\n{ y = c.x }\n");
+    server.wait_for_diagnostics(&b, |diagnostics| diagnostics.is_empty());
+    let a = open(&mut server,
+                 &dir,
+                 "a.glu",
+                 "let b = import \"b.glu\"\nb.y #Int+ 1\n");
+    server.wait_for_diagnostics(&a, |diagnostics| diagnostics.is_empty());
+
+    // `a.glu` only sees the new type of `y` if `b.glu` was checked before it
+    replace_first_line(&mut server, &c, "{ x = 1 }", "{ x = \"\" }");
+    server.wait_for_diagnostics(&a, |diagnostics| !diagnostics.is_empty());
+    server.exit();
+}
+
+#[test]
+fn importer_opened_after_the_imported_module_is_checked_again() {
+    let dir = support::temp_dir("dependents_loaded");
+    let mut server = support::Server::spawn();
+    support::initialize(server.stdin(), 1, &[uri(&dir)]);
+    server.wait_for_response(1);
+
+    // `b.glu` is already loaded when `a.glu` imports it
+    let b = open(&mut server, &dir, "b.glu", "{ y = 1 }\n");
+    server.wait_for_diagnostics(&b, |diagnostics| diagnostics.is_empty());
+    let a = open(&mut server,
+                 &dir,
+                 "a.glu",
+                 "let b = import \"b.glu\"\nb.y #Int+ 1\n");
+    server.wait_for_diagnostics(&a, |diagnostics| diagnostics.is_empty());
+
+    replace_first_line(&mut server, &b, "{ y = 1 }", "{ y = \"\" }");
+    server.wait_for_diagnostics(&a, |diagnostics| !diagnostics.is_empty());
+    server.exit();
+}
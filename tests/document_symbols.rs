@@ -0,0 +1,68 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+
+mod support;
+
+use std::io::Write;
+
+use serde_json::Value;
+
+use languageserver_types::{DocumentSymbolParams, TextDocumentIdentifier};
+
+fn document_symbols<W: ?Sized>(stdin: &mut W, id: u64, uri: &str)
+    where W: Write,
+{
+    let symbols = support::method_call("textDocument/documentSymbol",
+                                       id,
+                                       DocumentSymbolParams {
+                                           text_document: TextDocumentIdentifier {
+                                               uri: uri.into(),
+                                           },
+                                       });
+
+    support::write_message(stdin, symbols).unwrap();
+}
+
+fn names_and_kinds(symbols: &[Value]) -> Vec<(String, u64, Vec<(String, u64)>)> {
+    symbols.iter()
+        .map(|symbol| {
+            let symbol = symbol.as_object().expect("Object");
+            let children = names_and_kinds(symbol["children"].as_array().expect("Array"))
+                .into_iter()
+                .map(|(name, kind, _)| (name, kind))
+                .collect();
+            (symbol["name"].as_str().expect("String").to_string(),
+             symbol["kind"].as_u64().expect("Integer"),
+             children)
+        })
+        .collect()
+}
+
+#[test]
+fn outline() {
+    let symbols: Vec<Value> = support::send_rpc(|mut stdin| {
+        let src = r#"
+let test = 1
+let f x =
+    let g y = y
+    g x
+{ test, f }
+"#;
+        support::did_open(stdin, "test", src);
+
+        document_symbols(stdin, 2, "test");
+    });
+
+    const FIELD: u64 = 8;
+    const FUNCTION: u64 = 12;
+    const VARIABLE: u64 = 13;
+    assert_eq!(names_and_kinds(&symbols),
+               vec![("test".to_string(), VARIABLE, vec![]),
+                    ("f".to_string(), FUNCTION, vec![("g".to_string(), FUNCTION)]),
+                    ("test".to_string(), FIELD, vec![]),
+                    ("f".to_string(), FIELD, vec![])]);
+}
@@ -0,0 +1,217 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+
+mod support;
+
+use std::io::Write;
+
+use languageserver_types::{DocumentFormattingParams, DocumentRangeFormattingParams,
+                           FormattingOptions, Position, Range, TextDocumentIdentifier, TextEdit};
+
+fn options() -> FormattingOptions {
+    FormattingOptions {
+        tab_size: 4,
+        insert_spaces: true,
+    }
+}
+
+fn formatting<W: ?Sized>(stdin: &mut W, id: u64, uri: &str, options: FormattingOptions)
+    where W: Write,
+{
+    let formatting = support::method_call("textDocument/formatting",
+                                          id,
+                                          DocumentFormattingParams {
+                                              text_document: TextDocumentIdentifier {
+                                                  uri: uri.into(),
+                                              },
+                                              options: options,
+                                          });
+
+    support::write_message(stdin, formatting).unwrap();
+}
+
+fn range_formatting<W: ?Sized>(stdin: &mut W, id: u64, uri: &str, range: Range)
+    where W: Write,
+{
+    let formatting = support::method_call("textDocument/rangeFormatting",
+                                          id,
+                                          DocumentRangeFormattingParams {
+                                              text_document: TextDocumentIdentifier {
+                                                  uri: uri.into(),
+                                              },
+                                              range: range,
+                                              options: options(),
+                                          });
+
+    support::write_message(stdin, formatting).unwrap();
+}
+
+fn edit(line: u64, start: u64, end: u64, new_text: &str) -> TextEdit {
+    TextEdit {
+        range: Range {
+            start: Position {
+                line: line,
+                character: start,
+            },
+            end: Position {
+                line: line,
+                character: end,
+            },
+        },
+        new_text: new_text.into(),
+    }
+}
+
+/// Applies `edits`, which are sorted and do not overlap, to `text`
+fn apply_edits(text: &str, edits: &[TextEdit]) -> String {
+    let offset = |position: &Position| {
+        text.split('\n')
+            .take(position.line as usize)
+            .map(|line| line.len() + 1)
+            .sum::<usize>() + position.character as usize
+    };
+    let mut result = text.to_string();
+    for edit in edits.iter().rev() {
+        let start = offset(&edit.range.start);
+        let end = offset(&edit.range.end);
+        result = format!("{}{}{}", &result[..start], edit.new_text, &result[end..]);
+    }
+    result
+}
+
+fn format_text(src: &str, options: FormattingOptions) -> String {
+    let edits: Vec<TextEdit> = support::send_rpc(|mut stdin| {
+        support::did_open(stdin, "test", src);
+
+        formatting(stdin, 2, "test", options);
+    });
+    apply_edits(src, &edits)
+}
+
+#[test]
+fn canonical_layout() {
+    let src = r#"
+let f x =
+        x
+// Call f
+f   1   
+"#;
+    let expected = r#"let f x = x
+// Call f
+f 1
+"#;
+    assert_eq!(format_text(src, options()), expected);
+}
+
+#[test]
+fn comments_are_kept() {
+    let src = r#"
+// A record
+let r = { x = 1, // The x
+          y = 2 }
+
+/* The result */
+let { x } =
+    // Only x is used
+    r
+x // Done
+"#;
+    let expected = r#"// A record
+let r = {
+    x = 1, // The x
+    y = 2
+}
+
+/* The result */
+let { x } =
+    // Only x is used
+    r
+x // Done
+"#;
+    assert_eq!(format_text(src, options()), expected);
+}
+
+#[test]
+fn long_lines_are_broken() {
+    let src = "
+type Shape = | Circle Float | Rectangle Float Float
+let area shape =
+  match shape with
+  | Circle r -> 3.14 * r * r
+  | Rectangle w h -> w * h
+let total = area (Circle 100000000.0) + area (Rectangle 200000000.0 300000000.0) + \
+             area (Circle 10.0)
+let f = \\x -> if x > 1 then area (Circle 1.0) else area (Rectangle 2.0 (area (Circle 1.0))) + \
+         total + 1
+f
+";
+    let expected = r#"type Shape =
+	| Circle Float
+	| Rectangle Float Float
+let area shape =
+	match shape with
+	| Circle r -> 3.14 * r * r
+	| Rectangle w h -> w * h
+let total =
+	area (Circle 100000000.0) + area (Rectangle 200000000.0 300000000.0) + area (Circle 10.0)
+let f = \x ->
+	if x > 1 then area (Circle 1.0) else area (Rectangle 2.0 (area (Circle 1.0))) + total + 1
+f
+"#;
+    let options = FormattingOptions {
+        tab_size: 4,
+        insert_spaces: false,
+    };
+    assert_eq!(format_text(src, options), expected);
+}
+
+#[test]
+fn only_changed_lines_are_edited() {
+    let edits: Vec<TextEdit> = support::send_rpc(|mut stdin| {
+        let src = "let x = 1\n\nlet y = 2\nx  \n";
+        support::did_open(stdin, "test", src);
+
+        formatting(stdin, 2, "test", options());
+    });
+
+    assert_eq!(edits, [edit(3, 1, 3, "")]);
+}
+
+#[test]
+fn range_formatting_only_edits_the_range() {
+    let edits: Vec<TextEdit> = support::send_rpc(|mut stdin| {
+        let src = "let x =  1\nlet y =   2\nx  \n";
+        support::did_open(stdin, "test", src);
+
+        range_formatting(stdin,
+                         2,
+                         "test",
+                         Range {
+                             start: Position {
+                                 line: 1,
+                                 character: 0,
+                             },
+                             end: Position {
+                                 line: 1,
+                                 character: 11,
+                             },
+                         });
+    });
+
+    assert_eq!(edits, [edit(1, 8, 10, "")]);
+}
+
+#[test]
+fn unparseable_document_is_not_formatted() {
+    let error = support::send_rpc_error(|mut stdin| {
+        support::did_open(stdin, "test", "let x = \n");
+
+        formatting(stdin, 2, "test", options());
+    });
+
+    assert!(error.message.contains("does not parse"), "{}", error.message);
+}
@@ -116,3 +116,25 @@ fn stream() {
                    range: None,
                });
 }
+
+#[test]
+fn after_non_ascii() {
+    // Positions count UTF-16 code units unless the client asks for something else
+    let hover: Hover = support::send_rpc(|mut stdin| {
+        support::did_open(stdin, "test", r#"let text = "ä😀" in text"#);
+
+        hover(stdin,
+              2,
+              "test",
+              Position {
+                  line: 0,
+                  character: 22,
+              });
+    });
+
+    assert_eq!(hover,
+               Hover {
+                   contents: vec![MarkedString::String("String".into())],
+                   range: None,
+               });
+}
@@ -0,0 +1,78 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+extern crate url;
+
+mod support;
+
+use std::path::Path;
+
+use serde_json::Value;
+
+fn uri(path: &Path) -> String {
+    url::Url::from_file_path(path).unwrap().to_string()
+}
+
+fn message(diagnostic: &Value) -> &str {
+    diagnostic.find("message").and_then(|message| message.as_str()).unwrap_or("")
+}
+
+#[test]
+fn imported_errors_are_published_against_the_imported_file() {
+    let dir = support::temp_dir("import_errors_file");
+    let b = uri(&support::write_file(&dir, "b.glu", "let x : Int = \"\"\n{ x }\n"));
+    let a = uri(&dir.join("a.glu"));
+
+    let mut server = support::Server::spawn();
+    support::initialize(server.stdin(), 1, &[uri(&dir)]);
+    server.wait_for_response(1);
+    support::did_open(server.stdin(), &a, "let b = import \"b.glu\"\nb.x\n");
+
+    let imported = server.wait_for_diagnostics(&b, |diagnostics| !diagnostics.is_empty());
+    // The mismatch is reported at the binding of `x`
+    assert_eq!(imported[0].find_path(&["range", "start", "line"]).and_then(|line| line.as_u64()),
+               Some(0));
+
+    let diagnostics = server.wait_for_diagnostics(&a, |diagnostics| !diagnostics.is_empty());
+    assert_eq!(diagnostics.len(), 1);
+    let diagnostic = &diagnostics[0];
+    assert!(message(diagnostic).contains("`b`"), "{}", message(diagnostic));
+    // The diagnostic covers `import "b.glu"`
+    let position = |path: &[&str]| {
+        diagnostic.find_path(path).and_then(|value| value.as_u64()).unwrap()
+    };
+    assert_eq!((position(&["range", "start", "line"]), position(&["range", "start", "character"])),
+               (0, 8));
+    assert_eq!((position(&["range", "end", "line"]), position(&["range", "end", "character"])),
+               (0, 22));
+    server.exit();
+}
+
+#[test]
+fn errors_of_every_failing_import_are_published() {
+    let dir = support::temp_dir("import_errors_multiple");
+    let b = uri(&support::write_file(&dir, "b.glu", "let x : Int = \"\"\n{ x }\n"));
+    let c = uri(&support::write_file(&dir, "c.glu", "let y : String = 1\n{ y }\n"));
+    let a = uri(&dir.join("a.glu"));
+
+    let mut server = support::Server::spawn();
+    support::initialize(server.stdin(), 1, &[uri(&dir)]);
+    server.wait_for_response(1);
+    support::did_open(server.stdin(),
+                      &a,
+                      "let b = import \"b.glu\"\nlet c = import \"c.glu\"\nb.x\n");
+
+    server.wait_for_diagnostics(&b, |diagnostics| !diagnostics.is_empty());
+    server.wait_for_diagnostics(&c, |diagnostics| !diagnostics.is_empty());
+    let diagnostics = server.wait_for_diagnostics(&a, |diagnostics| !diagnostics.is_empty());
+    let lines: Vec<_> = diagnostics.iter()
+        .filter(|diagnostic| message(diagnostic).starts_with("Error in imported module"))
+        .filter_map(|diagnostic| diagnostic.find_path(&["range", "start", "line"]))
+        .filter_map(|line| line.as_u64())
+        .collect();
+    assert_eq!(lines, vec![0, 1]);
+    server.exit();
+}
@@ -0,0 +1,159 @@
+extern crate gluon;
+extern crate gluon_language_server;
+extern crate languageserver_types;
+
+use gluon::base::pos::{BytePos, Span};
+
+use languageserver_types::{Position, Range};
+
+use gluon_language_server::position::{Lines, PositionEncoding, byte_pos_to_position,
+                                      offset_to_position, position_to_byte_pos,
+                                      position_to_offset};
+
+const ENCODINGS: [PositionEncoding; 3] =
+    [PositionEncoding::Utf8, PositionEncoding::Utf16, PositionEncoding::Utf32];
+
+fn position(line: u64, character: u64) -> Position {
+    Position {
+        line: line,
+        character: character,
+    }
+}
+
+fn assert_round_trip(text: &str) {
+    for &encoding in &ENCODINGS {
+        for offset in (0..text.len() + 1).filter(|&offset| text.is_char_boundary(offset)) {
+            let pos = byte_pos_to_position(text, BytePos::from(offset), encoding);
+            assert_eq!(position_to_byte_pos(text, &pos, encoding),
+                       Some(BytePos::from(offset)),
+                       "{:?} at {} in {:?} ({:?})",
+                       pos,
+                       offset,
+                       text,
+                       encoding);
+
+            let pos = offset_to_position(text, offset, encoding);
+            assert_eq!(position_to_offset(text, &pos, encoding),
+                       Some(offset),
+                       "{:?} at {} in {:?} ({:?})",
+                       pos,
+                       offset,
+                       text,
+                       encoding);
+        }
+    }
+}
+
+#[test]
+fn round_trip() {
+    assert_round_trip("");
+    assert_round_trip("let x = 1");
+    assert_round_trip("let x = 1\nx");
+    assert_round_trip("let x = 1\nx\n");
+    assert_round_trip("\n\n");
+    assert_round_trip("let s = \"åäö\"\ns");
+    assert_round_trip("let s = \"😀\" in\r\ns");
+}
+
+#[test]
+fn first_line() {
+    let text = "let x = 1\nx";
+    for &encoding in &ENCODINGS {
+        assert_eq!(byte_pos_to_position(text, BytePos::from(4), encoding),
+                   position(0, 4));
+        assert_eq!(position_to_byte_pos(text, &position(0, 4), encoding),
+                   Some(BytePos::from(4)));
+        assert_eq!(offset_to_position(text, 0, encoding), position(0, 0));
+        assert_eq!(position_to_offset(text, &position(0, 9), encoding), Some(9));
+    }
+}
+
+#[test]
+fn last_line() {
+    let text = "let x = 1\nx\n";
+    for &encoding in &ENCODINGS {
+        assert_eq!(byte_pos_to_position(text, BytePos::from(10), encoding),
+                   position(1, 0));
+        assert_eq!(byte_pos_to_position(text, BytePos::from(text.len()), encoding),
+                   position(2, 0));
+        assert_eq!(position_to_byte_pos(text, &position(2, 0), encoding),
+                   Some(BytePos::from(text.len())));
+        assert_eq!(offset_to_position(text, text.len(), encoding), position(2, 0));
+        assert_eq!(position_to_offset(text, &position(1, 1), encoding), Some(11));
+        assert_eq!(position_to_offset(text, &position(3, 0), encoding), None);
+    }
+}
+
+#[test]
+fn empty_file() {
+    for &encoding in &ENCODINGS {
+        assert_eq!(byte_pos_to_position("", BytePos::from(0), encoding),
+                   position(0, 0));
+        assert_eq!(position_to_byte_pos("", &position(0, 0), encoding),
+                   Some(BytePos::from(0)));
+        assert_eq!(position_to_byte_pos("", &position(1, 0), encoding), None);
+        assert_eq!(offset_to_position("", 0, encoding), position(0, 0));
+        assert_eq!(position_to_offset("", &position(0, 0), encoding), Some(0));
+        assert_eq!(position_to_offset("", &position(1, 0), encoding), None);
+    }
+}
+
+#[test]
+fn non_ascii() {
+    // `ä` is 2 bytes in UTF-8 and `😀` is 4 bytes in UTF-8 and 2 code units in UTF-16
+    let text = "let s = \"ä😀\" in s";
+    let s = text.rfind('s').unwrap();
+    assert_eq!(offset_to_position(text, s, PositionEncoding::Utf8), position(0, 20));
+    assert_eq!(offset_to_position(text, s, PositionEncoding::Utf16), position(0, 17));
+    assert_eq!(offset_to_position(text, s, PositionEncoding::Utf32), position(0, 16));
+
+    assert_eq!(position_to_offset(text, &position(0, 17), PositionEncoding::Utf16),
+               Some(s));
+    assert_eq!(position_to_offset(text, &position(0, 16), PositionEncoding::Utf32),
+               Some(s));
+}
+
+#[test]
+fn lines() {
+    // Only the line of each position is scanned so the characters before it must not count
+    let text = "let a = \"😀\"\nlet b = \"ä\"\r\nb";
+    let lines = Lines::new(text);
+    let b = text.rfind('b').unwrap();
+    assert_eq!(lines.position(text, b, PositionEncoding::Utf16), position(2, 0));
+    assert_eq!(lines.offset(text, &position(2, 0), PositionEncoding::Utf16), Some(b));
+    assert_eq!(lines.offset(text, &position(3, 0), PositionEncoding::Utf16), None);
+
+    let quote = text.rfind('"').unwrap();
+    assert_eq!(lines.position(text, quote, PositionEncoding::Utf16), position(1, 10));
+    assert_eq!(lines.position(text, quote, PositionEncoding::Utf8), position(1, 11));
+    assert_eq!(lines.offset(text, &position(1, 10), PositionEncoding::Utf16), Some(quote));
+    assert_eq!(lines.range(text,
+                           Span::new(BytePos::from(quote), BytePos::from(b)),
+                           PositionEncoding::Utf16),
+               Range {
+                   start: position(1, 10),
+                   end: position(2, 0),
+               });
+}
+
+#[test]
+fn inside_character() {
+    let text = "\"😀\"";
+    // The middle of the surrogate pair
+    assert_eq!(position_to_offset(text, &position(0, 2), PositionEncoding::Utf16),
+               None);
+    assert_eq!(position_to_offset(text, &position(0, 2), PositionEncoding::Utf8),
+               None);
+    assert_eq!(position_to_offset(text, &position(0, 3), PositionEncoding::Utf16),
+               Some(5));
+}
+
+#[test]
+fn negotiate() {
+    assert_eq!(PositionEncoding::negotiate(Vec::<&str>::new()), PositionEncoding::Utf16);
+    assert_eq!(PositionEncoding::negotiate(vec!["utf-16", "utf-8"]),
+               PositionEncoding::Utf8);
+    assert_eq!(PositionEncoding::negotiate(vec!["utf-32", "utf-16"]),
+               PositionEncoding::Utf32);
+    assert_eq!(PositionEncoding::negotiate(vec!["latin-1"]), PositionEncoding::Utf16);
+}
@@ -0,0 +1,94 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+
+mod support;
+
+use std::io::Write;
+
+use languageserver_types::{Location, Position, Range, ReferenceContext, ReferenceParams,
+                           TextDocumentIdentifier};
+
+fn references<W: ?Sized>(stdin: &mut W,
+                         id: u64,
+                         uri: &str,
+                         position: Position,
+                         include_declaration: bool)
+    where W: Write,
+{
+    let references = support::method_call("textDocument/references",
+                                          id,
+                                          ReferenceParams {
+                                              text_document: TextDocumentIdentifier {
+                                                  uri: uri.into(),
+                                              },
+                                              position: position,
+                                              context: ReferenceContext {
+                                                  include_declaration: include_declaration,
+                                              },
+                                          });
+
+    support::write_message(stdin, references).unwrap();
+}
+
+fn location(line: u64, start: u64, end: u64) -> Location {
+    Location {
+        uri: "test".into(),
+        range: Range {
+            start: Position {
+                line: line,
+                character: start,
+            },
+            end: Position {
+                line: line,
+                character: end,
+            },
+        },
+    }
+}
+
+const SOURCE: &'static str = r#"
+let test = 1
+let test2 = test
+test
+"#;
+
+#[test]
+fn include_declaration() {
+    let locations: Vec<Location> = support::send_rpc(|mut stdin| {
+        support::did_open(stdin, "test", SOURCE);
+
+        references(stdin,
+                   2,
+                   "test",
+                   Position {
+                       line: 3,
+                       character: 2,
+                   },
+                   true);
+    });
+
+    assert_eq!(locations,
+               [location(1, 4, 8), location(2, 12, 16), location(3, 0, 4)]);
+}
+
+#[test]
+fn exclude_declaration() {
+    let locations: Vec<Location> = support::send_rpc(|mut stdin| {
+        support::did_open(stdin, "test", SOURCE);
+
+        references(stdin,
+                   2,
+                   "test",
+                   Position {
+                       line: 1,
+                       character: 5,
+                   },
+                   false);
+    });
+
+    assert_eq!(locations, [location(2, 12, 16), location(3, 0, 4)]);
+}
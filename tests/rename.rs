@@ -0,0 +1,232 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+
+mod support;
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use languageserver_types::{Position, Range, RenameParams, TextDocumentIdentifier,
+                           TextDocumentPositionParams, TextEdit, WorkspaceEdit};
+
+fn rename<W: ?Sized>(stdin: &mut W, id: u64, uri: &str, position: Position, new_name: &str)
+    where W: Write,
+{
+    let rename = support::method_call("textDocument/rename",
+                                      id,
+                                      RenameParams {
+                                          text_document: TextDocumentIdentifier {
+                                              uri: uri.into(),
+                                          },
+                                          position: position,
+                                          new_name: new_name.into(),
+                                      });
+
+    support::write_message(stdin, rename).unwrap();
+}
+
+fn position(line: u64, character: u64) -> Position {
+    Position {
+        line: line,
+        character: character,
+    }
+}
+
+/// Renames the symbol at `position` in `src` to `new_name`, returning the error response
+fn rename_error(src: &str, position: Position, new_name: &str) -> String {
+    let error = support::send_rpc_error(|mut stdin| {
+        support::did_open(stdin, "test", src);
+
+        rename(stdin, 2, "test", position, new_name);
+    });
+    error.message
+}
+
+fn edit(line: u64, start: u64, end: u64, new_text: &str) -> TextEdit {
+    TextEdit {
+        range: Range {
+            start: Position {
+                line: line,
+                character: start,
+            },
+            end: Position {
+                line: line,
+                character: end,
+            },
+        },
+        new_text: new_text.into(),
+    }
+}
+
+#[test]
+fn rename_let_binding() {
+    let workspace_edit: WorkspaceEdit = support::send_rpc(|mut stdin| {
+        let src = r#"
+let test = 1
+let test2 = test
+test
+"#;
+        support::did_open(stdin, "test", src);
+
+        rename(stdin,
+               2,
+               "test",
+               Position {
+                   line: 3,
+                   character: 2,
+               },
+               "renamed");
+    });
+
+    let mut changes = HashMap::new();
+    changes.insert("test".to_string(),
+                   vec![edit(1, 4, 8, "renamed"),
+                        edit(2, 12, 16, "renamed"),
+                        edit(3, 0, 4, "renamed")]);
+    assert_eq!(workspace_edit, WorkspaceEdit { changes: changes });
+}
+
+#[test]
+fn rename_function_argument() {
+    let workspace_edit: WorkspaceEdit = support::send_rpc(|mut stdin| {
+        let src = r#"
+let f x = x #Int+ 1
+f 2
+"#;
+        support::did_open(stdin, "test", src);
+
+        rename(stdin, 2, "test", position(1, 6), "y");
+    });
+
+    let mut changes = HashMap::new();
+    changes.insert("test".to_string(),
+                   vec![edit(1, 6, 7, "y"), edit(1, 10, 11, "y")]);
+    assert_eq!(workspace_edit, WorkspaceEdit { changes: changes });
+}
+
+#[test]
+fn rename_field_only_in_records_of_the_same_type() {
+    let workspace_edit: WorkspaceEdit = support::send_rpc(|mut stdin| {
+        let src = r#"
+let r = { x = 1, y = 2 }
+let s = { x = "" }
+r.x
+"#;
+        support::did_open(stdin, "test", src);
+
+        rename(stdin, 2, "test", position(3, 2), "z");
+    });
+
+    let mut changes = HashMap::new();
+    changes.insert("test".to_string(),
+                   vec![edit(1, 10, 11, "z"), edit(3, 2, 3, "z")]);
+    assert_eq!(workspace_edit, WorkspaceEdit { changes: changes });
+}
+
+#[test]
+fn rename_which_collides_is_refused() {
+    let message = rename_error(r#"
+let test = 1
+let other = 2
+test #Int+ other
+"#,
+                               position(3, 1),
+                               "other");
+    assert!(message.contains("collide"), "{}", message);
+}
+
+#[test]
+fn rename_type() {
+    let workspace_edit: WorkspaceEdit = support::send_rpc(|mut stdin| {
+        let src = r#"
+type Test = Int
+let x : Test = 1
+x
+"#;
+        support::did_open(stdin, "test", src);
+
+        rename(stdin, 2, "test", position(2, 10), "Renamed");
+    });
+
+    let mut changes = HashMap::new();
+    changes.insert("test".to_string(),
+                   vec![edit(1, 5, 9, "Renamed"), edit(2, 8, 12, "Renamed")]);
+    assert_eq!(workspace_edit, WorkspaceEdit { changes: changes });
+}
+
+#[test]
+fn rename_type_which_collides_is_refused() {
+    let message = rename_error(r#"
+type Test = Int
+type Other = Test
+let x : Test = 1
+x
+"#,
+                               position(1, 6),
+                               "Other");
+    assert!(message.contains("collide"), "{}", message);
+}
+
+#[test]
+fn rename_type_to_the_name_of_a_constructor() {
+    let workspace_edit: WorkspaceEdit = support::send_rpc(|mut stdin| {
+        let src = r#"
+type Test = Int
+type Option a = | None | Some a
+let x : Test = 1
+x
+"#;
+        support::did_open(stdin, "test", src);
+
+        rename(stdin, 2, "test", position(1, 6), "Some");
+    });
+
+    let mut changes = HashMap::new();
+    changes.insert("test".to_string(),
+                   vec![edit(1, 5, 9, "Some"), edit(3, 8, 12, "Some")]);
+    assert_eq!(workspace_edit, WorkspaceEdit { changes: changes });
+}
+
+#[test]
+fn prepare_rename() {
+    let range: Range = support::send_rpc(|mut stdin| {
+        let src = r#"
+let test = 1
+test
+"#;
+        support::did_open(stdin, "test", src);
+
+        let prepare = support::method_call("textDocument/prepareRename",
+                                           2,
+                                           TextDocumentPositionParams {
+                                               text_document: TextDocumentIdentifier {
+                                                   uri: "test".into(),
+                                               },
+                                               position: position(2, 1),
+                                           });
+        support::write_message(stdin, prepare).unwrap();
+    });
+
+    assert_eq!(range,
+               Range {
+                   start: position(2, 0),
+                   end: position(2, 4),
+               });
+}
+
+#[test]
+fn prepare_rename_is_advertised() {
+    let capabilities: serde_json::Value = support::send_rpc(|mut stdin| {
+        support::initialize(stdin, 1, &[]);
+    });
+
+    let prepare_provider = capabilities.find_path(&["capabilities",
+                                                    "renameProvider",
+                                                    "prepareProvider"]);
+    assert_eq!(prepare_provider.and_then(|prepare| prepare.as_bool()),
+               Some(true));
+}
@@ -0,0 +1,159 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+extern crate url;
+
+mod support;
+
+use std::path::Path;
+
+use languageserver_types::{Hover, Position, TextDocumentIdentifier, TextDocumentPositionParams};
+
+use gluon_language_server::settings::{Features, Lints, Settings};
+
+fn settings(json: &str) -> Settings {
+    Settings::from_value(&serde_json::from_str(json).unwrap())
+}
+
+fn uri(path: &Path) -> String {
+    url::Url::from_file_path(path).unwrap().to_string()
+}
+
+#[test]
+fn parse_settings() {
+    let settings = settings(r#"{
+        "gluon": {
+            "maxNumberOfProblems": 5,
+            "importPaths": ["a", "b"],
+            "features": { "hover": false },
+            "lints": { "shadowing": true, "unusedImport": false }
+        }
+    }"#);
+    assert_eq!(settings,
+               Settings {
+                   max_number_of_problems: 5,
+                   import_paths: vec!["a".to_string(), "b".to_string()],
+                   features: Features { hover: false, ..Features::default() },
+                   lints: Lints {
+                       shadowing: true,
+                       unused_import: false,
+                       ..Lints::default()
+                   },
+               });
+}
+
+#[test]
+fn invalid_settings_are_ignored() {
+    assert_eq!(settings(r#"{
+        "gluon": {
+            "maxNumberOfProblems": "5",
+            "importPaths": "a",
+            "features": { "hover": 0 }
+        }
+    }"#),
+               Settings::default());
+    assert_eq!(settings("null"), Settings::default());
+}
+
+#[test]
+fn max_number_of_problems() {
+    let mut server = support::Server::spawn();
+    support::did_change_configuration(server.stdin(),
+                                      r#"{ "gluon": { "maxNumberOfProblems": 1 } }"#);
+    support::did_open(server.stdin(), "test", "let x = y\nlet z = w\nx");
+
+    let diagnostics = server.wait_for_diagnostics("test", |diagnostics| !diagnostics.is_empty());
+    assert_eq!(diagnostics.len(), 1);
+    server.exit();
+}
+
+fn codes_and_lines(diagnostics: &[serde_json::Value]) -> Vec<(Option<&str>, Option<u64>)> {
+    diagnostics.iter()
+        .map(|diagnostic| {
+            (diagnostic.find("code").and_then(|code| code.as_str()),
+             diagnostic.find_path(&["range", "start", "line"]).and_then(|line| line.as_u64()))
+        })
+        .collect()
+}
+
+#[test]
+fn enabled_shadowing_lint() {
+    let mut server = support::Server::spawn();
+    support::did_change_configuration(server.stdin(),
+                                      r#"{ "gluon": { "lints": { "shadowing": true } } }"#);
+    support::did_open(server.stdin(), "test", "let y = 1\nlet x = y\nlet x = x\nx\n");
+
+    let diagnostics = server.wait_for_diagnostics("test", |diagnostics| !diagnostics.is_empty());
+    assert_eq!(codes_and_lines(&diagnostics), vec![(Some("shadowing"), Some(2))]);
+    server.exit();
+}
+
+#[test]
+fn disabled_lint() {
+    let mut server = support::Server::spawn();
+    support::did_change_configuration(server.stdin(),
+                                      r#"{ "gluon": { "lints": { "unusedBinding": false } } }"#);
+    support::did_open(server.stdin(),
+                      "test",
+                      "let unused = 1\nlet m = import \"std/prelude.glu\"\n1\n");
+
+    let diagnostics = server.wait_for_diagnostics("test", |_| true);
+    assert_eq!(codes_and_lines(&diagnostics), vec![(Some("unused-import"), Some(1))]);
+    server.exit();
+}
+
+#[test]
+fn disabled_hover() {
+    let hover: Hover = support::send_rpc(|mut stdin| {
+        support::did_change_configuration(stdin,
+                                          r#"{ "gluon": { "features": { "hover": false } } }"#);
+        support::did_open(stdin, "test", "let x = 1\nx");
+
+        let hover = support::method_call("textDocument/hover",
+                                         2,
+                                         TextDocumentPositionParams {
+                                             text_document: TextDocumentIdentifier {
+                                                 uri: "test".into(),
+                                             },
+                                             position: Position {
+                                                 line: 1,
+                                                 character: 0,
+                                             },
+                                         });
+        support::write_message(stdin, hover).unwrap();
+    });
+
+    assert_eq!(hover,
+               Hover {
+                   contents: vec![],
+                   range: None,
+               });
+}
+
+#[test]
+fn changed_import_paths_reload_modules() {
+    let first = support::temp_dir("settings_first");
+    let second = support::temp_dir("settings_second");
+    let main_dir = support::temp_dir("settings_main");
+    support::write_file(&first, "settings_module.glu", "{ x = 1 }\n");
+    support::write_file(&second, "settings_module.glu", "{ x = \"\" }\n");
+    let text = "let m = import \"settings_module.glu\"\nm.x #Int+ 1\n";
+    let main = uri(&support::write_file(&main_dir, "main.glu", text));
+
+    let mut server = support::Server::spawn();
+    support::did_change_configuration(server.stdin(),
+                                      &format!(r#"{{ "gluon": {{ "importPaths": ["{}"] }} }}"#,
+                                               first.display()));
+    support::did_open(server.stdin(), &main, text);
+    server.wait_for_diagnostics(&main, |diagnostics| diagnostics.is_empty());
+
+    // The module loaded from `first` must not be used after the paths change
+    support::did_change_configuration(server.stdin(),
+                                      &format!(r#"{{ "gluon": {{ "importPaths": ["{}"] }} }}"#,
+                                               second.display()));
+    server.wait_for_diagnostics(&main, |diagnostics| !diagnostics.is_empty());
+    server.exit();
+}
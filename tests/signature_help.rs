@@ -0,0 +1,101 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+
+mod support;
+
+use std::io::Write;
+
+use languageserver_types::{ParameterInformation, Position, Range, SignatureHelp,
+                           SignatureInformation, TextDocumentPositionParams,
+                           TextDocumentIdentifier};
+
+fn signature_help<W: ?Sized>(stdin: &mut W, id: u64, uri: &str, position: Position)
+    where W: Write,
+{
+    let signature_help = support::method_call("textDocument/signatureHelp",
+                                              id,
+                                              TextDocumentPositionParams {
+                                                  text_document: TextDocumentIdentifier {
+                                                      uri: uri.into(),
+                                                  },
+                                                  position: position,
+                                              });
+
+    support::write_message(stdin, signature_help).unwrap();
+}
+
+fn parameter(label: &str) -> ParameterInformation {
+    ParameterInformation {
+        label: label.into(),
+        documentation: None,
+    }
+}
+
+#[test]
+fn second_argument() {
+    let help: SignatureHelp = support::send_rpc(|mut stdin| {
+        let src = r#"
+let f x y : Int -> String -> Int = x
+f 1 
+"#;
+        support::did_open(stdin, "test", src);
+
+        signature_help(stdin,
+                       2,
+                       "test",
+                       Position {
+                           line: 2,
+                           character: 4,
+                       });
+    });
+
+    assert_eq!(help,
+               SignatureHelp {
+                   signatures: vec![SignatureInformation {
+                                        label: "f : Int -> String -> Int".into(),
+                                        documentation: None,
+                                        parameters: Some(vec![parameter("Int"),
+                                                              parameter("String")]),
+                                    }],
+                   active_signature: Some(0),
+                   active_parameter: Some(1),
+               });
+}
+
+#[test]
+fn application_typed_since_open() {
+    let help: SignatureHelp = support::send_rpc(|mut stdin| {
+        let src = r#"
+let f x y : Int -> String -> Int = x
+f
+"#;
+        support::did_open(stdin, "test", src);
+        let end = Position {
+            line: 2,
+            character: 1,
+        };
+        support::did_change(stdin,
+                            "test",
+                            2,
+                            Range {
+                                start: end,
+                                end: end,
+                            },
+                            " 1 ");
+
+        signature_help(stdin,
+                       2,
+                       "test",
+                       Position {
+                           line: 2,
+                           character: 4,
+                       });
+    });
+
+    assert_eq!(help.active_parameter, Some(1));
+    assert_eq!(help.signatures[0].label, "f : Int -> String -> Int");
+}
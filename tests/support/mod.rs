@@ -1,9 +1,17 @@
+#![allow(dead_code)]
+
 use std::env;
-use std::io::{self, Write};
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
 use std::str;
+use std::sync::atomic::{ATOMIC_USIZE_INIT, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use jsonrpc_core::Error;
 use jsonrpc_core::request::{Call, MethodCall, Notification};
 use jsonrpc_core::version::Version;
 use jsonrpc_core::params::Params;
@@ -14,7 +22,9 @@ use serde::{Deserialize, Serialize};
 use serde_json::ser::Serializer;
 use serde_json::{Value, to_value, from_str, from_value};
 
-use languageserver_types::{DidOpenTextDocumentParams, TextDocumentItem};
+use languageserver_types::{DidChangeTextDocumentParams, DidOpenTextDocumentParams, Range,
+                           TextDocumentContentChangeEvent, TextDocumentItem,
+                           VersionedTextDocumentIdentifier};
 
 use gluon_language_server::read_message;
 
@@ -64,12 +74,18 @@ pub fn notification<T>(method: &str, value: T) -> Call
 
 pub fn did_open<W: ?Sized>(stdin: &mut W, uri: &str, text: &str)
     where W: Write,
+{
+    did_open_language(stdin, uri, "gluon", text)
+}
+
+pub fn did_open_language<W: ?Sized>(stdin: &mut W, uri: &str, language_id: &str, text: &str)
+    where W: Write,
 {
     let did_open = notification("textDocument/didOpen",
                                 DidOpenTextDocumentParams {
                                     text_document: TextDocumentItem {
                                         uri: uri.into(),
-                                        language_id: Some("gluon".into()),
+                                        language_id: Some(language_id.into()),
                                         text: text.into(),
                                         version: Some(1),
                                     },
@@ -78,15 +94,139 @@ pub fn did_open<W: ?Sized>(stdin: &mut W, uri: &str, text: &str)
     write_message(stdin, did_open).unwrap();
 }
 
-pub fn send_rpc<F, T>(f: F) -> T
-    where F: FnOnce(&mut Write),
-          T: Deserialize,
+pub fn did_change<W: ?Sized>(stdin: &mut W, uri: &str, version: u64, range: Range, text: &str)
+    where W: Write,
 {
+    let did_change = notification("textDocument/didChange",
+                                  DidChangeTextDocumentParams {
+                                      text_document: VersionedTextDocumentIdentifier {
+                                          uri: uri.into(),
+                                          version: version,
+                                      },
+                                      content_changes: vec![TextDocumentContentChangeEvent {
+                                                                range: Some(range),
+                                                                range_length: None,
+                                                                text: text.into(),
+                                                            }],
+                                  });
+
+    write_message(stdin, did_change).unwrap();
+}
+
+/// Sends `workspace/didChangeConfiguration` with `settings`, the json of the client's settings
+pub fn did_change_configuration<W: ?Sized>(stdin: &mut W, settings: &str)
+    where W: Write,
+{
+    let params = format!(r#"{{ "settings": {} }}"#, settings);
+    let did_change = notification("workspace/didChangeConfiguration",
+                                  from_str::<Value>(&params).unwrap());
+    write_message(stdin, did_change).unwrap();
+}
+
+/// Sends an `initialize` request with the first of `folders` (uris) as the root of the workspace
+pub fn initialize<W: ?Sized>(stdin: &mut W, id: u64, folders: &[String])
+    where W: Write,
+{
+    initialize_with_capabilities(stdin, id, folders, "{}")
+}
+
+/// Sends an `initialize` request for a client with `capabilities` (json)
+pub fn initialize_with_capabilities<W: ?Sized>(stdin: &mut W,
+                                               id: u64,
+                                               folders: &[String],
+                                               capabilities: &str)
+    where W: Write,
+{
+    let folders: Vec<_> = folders.iter()
+        .map(|uri| format!(r#"{{ "uri": "{}", "name": "{}" }}"#, uri, uri))
+        .collect();
+    let params = format!(r#"{{
+            "processId": null,
+            "rootPath": null,
+            "capabilities": {},
+            "workspaceFolders": [{}]
+        }}"#,
+                         capabilities,
+                         folders.join(", "));
+    let initialize = method_call("initialize", id, from_str::<Value>(&params).unwrap());
+    write_message(stdin, initialize).unwrap();
+}
+
+/// Creates a new, empty directory which is not shared with any other test
+pub fn temp_dir(name: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = ATOMIC_USIZE_INIT;
+    loop {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos();
+        let path = env::temp_dir().join(format!("gluon_language_server_{}_{}_{}",
+                                                name,
+                                                nanos,
+                                                COUNTER.fetch_add(1, Ordering::SeqCst)));
+        if fs::create_dir(&path).is_ok() {
+            return fs::canonicalize(path).unwrap();
+        }
+    }
+}
+
+pub fn write_file(dir: &Path, name: &str, contents: &str) -> PathBuf {
+    let path = dir.join(name);
+    File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+    path
+}
+
+pub fn server_path() -> PathBuf {
     let args: Vec<_> = env::args().collect();
-    let server_path =
-        Path::new(&args[0][..]).parent().expect("folder").join("gluon_language-server");
+    Path::new(&args[0][..]).parent().expect("folder").join("gluon_language-server")
+}
 
-    let mut child = Command::new(server_path)
+pub fn exit<W: ?Sized>(stdin: &mut W)
+    where W: Write,
+{
+    let exit = Call::Notification(Notification {
+        jsonrpc: Version::V2,
+        method: "exit".into(),
+        params: None,
+    });
+    write_message(stdin, exit).unwrap();
+}
+
+/// Returns the result of the last successful response in `output`
+pub fn last_response<T>(output: &[u8]) -> T
+    where T: Deserialize,
+{
+    let mut value = None;
+    let mut messages = output;
+    while let Some(json) = read_message(&mut messages).unwrap() {
+        if let Ok(SyncResponse::Single(SyncOutput::Success(response))) = from_str(&json) {
+            value = from_value(response.result).ok();
+        }
+    }
+    value.unwrap_or_else(|| {
+        panic!("Could not find the retrieve the expected response out of:\n`{}`",
+               str::from_utf8(output).expect("UTF8"))
+    })
+}
+
+/// Returns the error of the last failed response in `output`
+pub fn last_error(output: &[u8]) -> Error {
+    let mut error = None;
+    let mut messages = output;
+    while let Some(json) = read_message(&mut messages).unwrap() {
+        if let Ok(SyncResponse::Single(SyncOutput::Failure(response))) = from_str(&json) {
+            error = Some(response.error);
+        }
+    }
+    error.unwrap_or_else(|| {
+        panic!("Expected an error response in:\n`{}`",
+               str::from_utf8(output).expect("UTF8"))
+    })
+}
+
+/// Runs a server, sends the messages written by `f` followed by `exit` and returns everything the
+/// server wrote
+pub fn send_rpc_output<F>(f: F) -> Vec<u8>
+    where F: FnOnce(&mut Write),
+{
+    let mut child = Command::new(server_path())
         .arg("--quiet")
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
@@ -94,30 +234,119 @@ pub fn send_rpc<F, T>(f: F) -> T
         .unwrap();
 
     {
-        let mut stdin = child.stdin.as_mut().expect("stdin");
+        let stdin = child.stdin.as_mut().expect("stdin");
 
         f(stdin);
 
-        let exit = Call::Notification(Notification {
-            jsonrpc: Version::V2,
-            method: "exit".into(),
-            params: None,
-        });
-        write_message(&mut stdin, exit).unwrap();
+        exit(stdin);
     }
 
     let result = child.wait_with_output().unwrap();
     assert!(result.status.success());
 
-    let mut value = None;
-    let mut output = &result.stdout[..];
-    while let Some(json) = read_message(&mut output).unwrap() {
-        if let Ok(SyncResponse::Single(SyncOutput::Success(response))) = from_str(&json) {
-            value = from_value(response.result).ok();
+    result.stdout
+}
+
+pub fn send_rpc<F, T>(f: F) -> T
+    where F: FnOnce(&mut Write),
+          T: Deserialize,
+{
+    last_response(&send_rpc_output(f))
+}
+
+pub fn send_rpc_error<F>(f: F) -> Error
+    where F: FnOnce(&mut Write),
+{
+    last_error(&send_rpc_output(f))
+}
+
+/// How long `Server::wait_for` waits for a message before failing the test
+const TIMEOUT: u64 = 20;
+
+/// A running server which messages can be exchanged with one at a time, for tests which need to
+/// wait for notifications the server sends on its own
+pub struct Server {
+    child: Child,
+    messages: Receiver<Value>,
+}
+
+impl Server {
+    pub fn spawn() -> Server {
+        let mut child = Command::new(server_path())
+            .arg("--quiet")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdout = child.stdout.take().expect("stdout");
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut stdout = BufReader::new(stdout);
+            while let Ok(Some(json)) = read_message(&mut stdout) {
+                if sender.send(from_str(&json).unwrap()).is_err() {
+                    return;
+                }
+            }
+        });
+        Server {
+            child: child,
+            messages: receiver,
         }
     }
-    value.unwrap_or_else(|| {
-        panic!("Could not find the retrieve the expected response out of:\n`{}`",
-               str::from_utf8(&result.stdout).expect("UTF8"))
-    })
+
+    pub fn stdin(&mut self) -> &mut ChildStdin {
+        self.child.stdin.as_mut().expect("stdin")
+    }
+
+    /// Returns the first message from the server which `predicate` accepts, skipping every message
+    /// before it
+    pub fn wait_for<F>(&mut self, mut predicate: F) -> Value
+        where F: FnMut(&Value) -> bool,
+    {
+        loop {
+            match self.messages.recv_timeout(Duration::from_secs(TIMEOUT)) {
+                Ok(message) => {
+                    if predicate(&message) {
+                        return message;
+                    }
+                }
+                Err(err) => panic!("Expected message did not arrive: {:?}", err),
+            }
+        }
+    }
+
+    /// Returns the response to the request `id`
+    pub fn wait_for_response(&mut self, id: u64) -> Value {
+        self.wait_for(|message| {
+            message.find("id").and_then(|message_id| message_id.as_u64()) == Some(id) &&
+            message.find("method").is_none()
+        })
+    }
+
+    /// Returns the diagnostics of the next `textDocument/publishDiagnostics` for `uri` which
+    /// `predicate` accepts
+    pub fn wait_for_diagnostics<F>(&mut self, uri: &str, mut predicate: F) -> Vec<Value>
+        where F: FnMut(&[Value]) -> bool,
+    {
+        let message = self.wait_for(|message| {
+            let params = message.find("params");
+            message.find("method").and_then(|method| method.as_str()) ==
+            Some("textDocument/publishDiagnostics") &&
+            params.and_then(|params| params.find("uri")).and_then(|uri| uri.as_str()) ==
+            Some(uri) &&
+            params.and_then(|params| params.find("diagnostics"))
+                .and_then(|diagnostics| diagnostics.as_array())
+                .map_or(false, |diagnostics| predicate(diagnostics))
+        });
+        message.find_path(&["params", "diagnostics"])
+            .and_then(|diagnostics| diagnostics.as_array())
+            .cloned()
+            .unwrap_or_else(Vec::new)
+    }
+
+    /// Sends `exit` and waits for the server to stop
+    pub fn exit(mut self) {
+        exit(self.stdin());
+        assert!(self.child.wait().unwrap().success());
+    }
 }
@@ -0,0 +1,55 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+
+mod support;
+
+use std::io::Read;
+use std::net::TcpListener;
+use std::process::Command;
+
+use languageserver_types::{Hover, MarkedString, Position, TextDocumentPositionParams,
+                           TextDocumentIdentifier};
+
+#[test]
+fn connect() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let mut child = Command::new(support::server_path())
+        .arg("--connect")
+        .arg(addr.to_string())
+        .spawn()
+        .unwrap();
+
+    let (mut stream, _) = listener.accept().unwrap();
+    support::did_open(&mut stream, "test", "let x = 1\nx");
+    let hover = support::method_call("textDocument/hover",
+                                     2,
+                                     TextDocumentPositionParams {
+                                         text_document: TextDocumentIdentifier {
+                                             uri: "test".into(),
+                                         },
+                                         position: Position {
+                                             line: 1,
+                                             character: 0,
+                                         },
+                                     });
+    support::write_message(&mut stream, hover).unwrap();
+    support::exit(&mut stream);
+
+    // The server closes the connection after `exit`
+    let mut output = Vec::new();
+    stream.read_to_end(&mut output).unwrap();
+    assert!(child.wait().unwrap().success());
+
+    let hover: Hover = support::last_response(&output);
+    assert_eq!(hover,
+               Hover {
+                   contents: vec![MarkedString::String("Int".into())],
+                   range: None,
+               });
+}
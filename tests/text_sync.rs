@@ -0,0 +1,181 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+extern crate url;
+
+mod support;
+
+use std::io::Write;
+use std::path::Path;
+
+use languageserver_types::{DidCloseTextDocumentParams, DidSaveTextDocumentParams, Hover,
+                           MarkedString, Position, Range, TextDocumentPositionParams,
+                           TextDocumentIdentifier};
+
+fn hover<W: ?Sized>(stdin: &mut W, id: u64, uri: &str, position: Position)
+    where W: Write,
+{
+    let hover = support::method_call("textDocument/hover",
+                                     id,
+                                     TextDocumentPositionParams {
+                                         text_document: TextDocumentIdentifier { uri: uri.into() },
+                                         position: position,
+                                     });
+
+    support::write_message(stdin, hover).unwrap();
+}
+
+fn did_close<W: ?Sized>(stdin: &mut W, uri: &str)
+    where W: Write,
+{
+    let did_close = support::notification("textDocument/didClose",
+                                          DidCloseTextDocumentParams {
+                                              text_document: TextDocumentIdentifier {
+                                                  uri: uri.into(),
+                                              },
+                                          });
+
+    support::write_message(stdin, did_close).unwrap();
+}
+
+fn did_save<W: ?Sized>(stdin: &mut W, uri: &str)
+    where W: Write,
+{
+    let did_save = support::notification("textDocument/didSave",
+                                         DidSaveTextDocumentParams {
+                                             text_document: TextDocumentIdentifier {
+                                                 uri: uri.into(),
+                                             },
+                                         });
+
+    support::write_message(stdin, did_save).unwrap();
+}
+
+fn uri(path: &Path) -> String {
+    url::Url::from_file_path(path).unwrap().to_string()
+}
+
+fn range(line: u64, start: u64, end: u64) -> Range {
+    Range {
+        start: Position {
+            line: line,
+            character: start,
+        },
+        end: Position {
+            line: line,
+            character: end,
+        },
+    }
+}
+
+#[test]
+fn incremental_change() {
+    let hover: Hover = support::send_rpc(|mut stdin| {
+        support::did_open(stdin, "test", "let x = 1\nx");
+        support::did_change(stdin, "test", 2, range(0, 8, 9), "\"abc\"");
+
+        hover(stdin,
+              2,
+              "test",
+              Position {
+                  line: 1,
+                  character: 0,
+              });
+    });
+
+    assert_eq!(hover,
+               Hover {
+                   contents: vec![MarkedString::String("String".into())],
+                   range: None,
+               });
+}
+
+#[test]
+fn out_of_order_change_is_ignored() {
+    let hover: Hover = support::send_rpc(|mut stdin| {
+        support::did_open(stdin, "test", "let x = 1\nx");
+        support::did_change(stdin, "test", 3, range(0, 8, 9), "\"abc\"");
+        // Version 2 arrives after version 3 and must not be applied
+        support::did_change(stdin, "test", 2, range(0, 8, 13), "1.0");
+
+        hover(stdin,
+              2,
+              "test",
+              Position {
+                  line: 1,
+                  character: 0,
+              });
+    });
+
+    assert_eq!(hover,
+               Hover {
+                   contents: vec![MarkedString::String("String".into())],
+                   range: None,
+               });
+}
+
+#[test]
+fn close_clears_diagnostics() {
+    let mut server = support::Server::spawn();
+    support::did_open(server.stdin(), "test", "let x = 1\nx #Int+ \"\"");
+    server.wait_for_diagnostics("test", |diagnostics| !diagnostics.is_empty());
+
+    did_close(server.stdin(), "test");
+    server.wait_for_diagnostics("test", |diagnostics| diagnostics.is_empty());
+    server.exit();
+}
+
+#[test]
+fn save_checks_the_document() {
+    let mut server = support::Server::spawn();
+    support::did_open(server.stdin(), "test", "let x = 1\nx #Int+ \"\"");
+    server.wait_for_diagnostics("test", |diagnostics| !diagnostics.is_empty());
+
+    did_save(server.stdin(), "test");
+    server.wait_for_diagnostics("test", |diagnostics| !diagnostics.is_empty());
+    server.exit();
+}
+
+#[test]
+fn foreign_documents_are_not_checked() {
+    let mut server = support::Server::spawn();
+    support::did_open_language(server.stdin(), "notes", "plaintext", "not gluon (");
+    did_save(server.stdin(), "notes");
+    support::did_open(server.stdin(), "test", "let x = 1\nx #Int+ \"\"");
+
+    // Documents are checked in order so any diagnostics for `notes` would arrive first
+    let diagnostics = server.wait_for(|message| {
+        message.find("method").and_then(|method| method.as_str()) ==
+        Some("textDocument/publishDiagnostics")
+    });
+    assert_eq!(diagnostics.find_path(&["params", "uri"]).and_then(|uri| uri.as_str()),
+               Some("test"));
+    server.exit();
+}
+
+#[test]
+fn closed_document_is_checked_from_disk() {
+    let dir = support::temp_dir("text_sync_close");
+    let a = uri(&support::write_file(&dir, "a.glu", "let b = import \"b.glu\"\nb.x #Int+ 1\n"));
+    let b = uri(&support::write_file(&dir, "b.glu", "{ x = 1 }\n"));
+
+    let mut server = support::Server::spawn();
+    support::initialize(server.stdin(), 1, &[uri(&dir)]);
+    server.wait_for_response(1);
+    support::did_open(server.stdin(),
+                      &a,
+                      "let b = import \"b.glu\"\nb.x #Int+ 1\n");
+    server.wait_for_diagnostics(&a, |diagnostics| diagnostics.is_empty());
+
+    // An edit which is never saved breaks `a.glu`
+    support::did_open(server.stdin(), &b, "{ x = \"\" }\n");
+    server.wait_for_diagnostics(&a, |diagnostics| !diagnostics.is_empty());
+
+    // Closing `b.glu` discards the edit so `a.glu` works again
+    did_close(server.stdin(), &b);
+    server.wait_for_diagnostics(&a, |diagnostics| diagnostics.is_empty());
+    server.exit();
+}
@@ -0,0 +1,119 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+extern crate url;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+
+mod support;
+
+use std::io::Write;
+
+use serde_json::Value;
+
+use languageserver_types::{DidChangeWatchedFilesParams, FileChangeType, FileEvent, Hover,
+                           MarkedString, Position, TextDocumentPositionParams,
+                           TextDocumentIdentifier};
+
+fn method(message: &Value) -> Option<&str> {
+    message.find("method").and_then(|method| method.as_str())
+}
+
+/// Sends `initialized` followed by a `shutdown` request and returns every message up to the
+/// response to `shutdown`
+fn initialized(server: &mut support::Server) -> Vec<Value> {
+    let initialized = support::notification("initialized", Value::Object(Default::default()));
+    support::write_message(server.stdin(), initialized).unwrap();
+    let shutdown = support::method_call("shutdown", 2, Value::Object(Default::default()));
+    support::write_message(server.stdin(), shutdown).unwrap();
+
+    let mut messages = Vec::new();
+    server.wait_for(|message| {
+        messages.push(message.clone());
+        message.find("id").and_then(|id| id.as_u64()) == Some(2) && method(message).is_none()
+    });
+    messages
+}
+
+#[test]
+fn created_file_is_checked() {
+    let dir = support::temp_dir("watched");
+    let path = support::write_file(&dir, "watched.glu", "let x = 1\nx\n");
+    let uri = url::Url::from_file_path(&path).unwrap().to_string();
+
+    let hover: Hover = support::send_rpc(|mut stdin| {
+        let changed = support::notification("workspace/didChangeWatchedFiles",
+                                            DidChangeWatchedFilesParams {
+                                                changes: vec![FileEvent {
+                                                                  uri: uri.clone(),
+                                                                  typ: FileChangeType::Created,
+                                                              }],
+                                            });
+        support::write_message(&mut stdin, changed).unwrap();
+
+        let hover = support::method_call("textDocument/hover",
+                                         2,
+                                         TextDocumentPositionParams {
+                                             text_document: TextDocumentIdentifier {
+                                                 uri: uri.clone(),
+                                             },
+                                             position: Position {
+                                                 line: 1,
+                                                 character: 0,
+                                             },
+                                         });
+        support::write_message(&mut stdin, hover).unwrap();
+    });
+
+    assert_eq!(hover,
+               Hover {
+                   contents: vec![MarkedString::String("Int".into())],
+                   range: None,
+               });
+}
+
+#[test]
+fn file_watcher_is_registered_if_the_client_supports_it() {
+    let mut server = support::Server::spawn();
+    let capabilities = r#"{
+            "workspace": { "didChangeWatchedFiles": { "dynamicRegistration": true } }
+        }"#;
+    support::initialize_with_capabilities(server.stdin(), 1, &[], capabilities);
+    server.wait_for_response(1);
+
+    let messages = initialized(&mut server);
+    assert!(messages.iter().any(|message| method(message) == Some("client/registerCapability")));
+    server.exit();
+}
+
+#[test]
+fn file_watcher_is_not_registered_without_dynamic_registration() {
+    let mut server = support::Server::spawn();
+    support::initialize(server.stdin(), 1, &[]);
+    server.wait_for_response(1);
+
+    let messages = initialized(&mut server);
+    assert!(!messages.iter().any(|message| method(message) == Some("client/registerCapability")));
+    server.exit();
+}
+
+#[test]
+fn responses_are_ignored_but_invalid_messages_are_answered() {
+    let mut server = support::Server::spawn();
+    // The response to the registration of the file watcher
+    let response = serde_json::from_str::<Value>(r#"{ "jsonrpc": "2.0", "id": 1, "result": null }"#)
+        .unwrap();
+    support::write_message(server.stdin(), response).unwrap();
+    let invalid = "{ not json";
+    write!(server.stdin(),
+           "Content-Length: {}\r\n\r\n{}",
+           invalid.len(),
+           invalid)
+        .unwrap();
+
+    let error = server.wait_for(|message| message.find("error").is_some());
+    assert_eq!(error.find_path(&["error", "code"]).and_then(|code| code.as_i64()),
+               Some(-32700));
+    server.exit();
+}
@@ -0,0 +1,118 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+extern crate url;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+
+mod support;
+
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use languageserver_types::{Hover, MarkedString, Position, TextDocumentPositionParams,
+                           TextDocumentIdentifier};
+
+fn folder(name: &str, source: &str) -> PathBuf {
+    let path = support::temp_dir(name);
+    support::write_file(&path, "module.glu", source);
+    path
+}
+
+fn uri(path: &Path) -> String {
+    url::Url::from_file_path(path).unwrap().to_string()
+}
+
+/// Hovers the expression on the second line of `module.glu` in `folder`
+fn hover(server: &mut support::Server, id: u64, folder: &Path) -> Value {
+    let hover = support::method_call("textDocument/hover",
+                                     id,
+                                     TextDocumentPositionParams {
+                                         text_document: TextDocumentIdentifier {
+                                             uri: uri(&folder.join("module.glu")),
+                                         },
+                                         position: Position {
+                                             line: 1,
+                                             character: 0,
+                                         },
+                                     });
+    support::write_message(server.stdin(), hover).unwrap();
+    server.wait_for_response(id)
+}
+
+fn hover_type(response: &Value) -> Hover {
+    serde_json::from_value(response.find("result").expect("result").clone()).unwrap()
+}
+
+/// Hovers `module.glu` in `folder` until the folder has been indexed, which happens in the
+/// background. `id` is the id of the last request sent.
+fn indexed_hover(server: &mut support::Server, id: &mut u64, folder: &Path) -> Hover {
+    for _ in 0..100 {
+        *id += 1;
+        let response = hover(server, *id, folder);
+        if response.find("result").is_some() {
+            return hover_type(&response);
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    panic!("`{}` was not indexed", folder.display())
+}
+
+fn type_hover(typ: &str) -> Hover {
+    Hover {
+        contents: vec![MarkedString::String(typ.into())],
+        range: None,
+    }
+}
+
+fn change_folders(server: &mut support::Server, added: &[&Path], removed: &[&Path]) {
+    let folders = |paths: &[&Path]| {
+        paths.iter()
+            .map(|path| format!(r#"{{ "uri": "{}", "name": "{}" }}"#, uri(path), uri(path)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let params = format!(r#"{{ "event": {{ "added": [{}], "removed": [{}] }} }}"#,
+                         folders(added),
+                         folders(removed));
+    let changed = support::notification("workspace/didChangeWorkspaceFolders",
+                                        serde_json::from_str::<Value>(&params).unwrap());
+    support::write_message(server.stdin(), changed).unwrap();
+}
+
+#[test]
+fn modules_with_the_same_name_in_different_folders() {
+    let first = folder("folder_first", "let x = 1\nx\n");
+    let second = folder("folder_second", "let x = \"\"\nx\n");
+
+    let mut server = support::Server::spawn();
+    support::initialize(server.stdin(), 1, &[uri(&first), uri(&second)]);
+    server.wait_for_response(1);
+
+    let mut id = 1;
+    assert_eq!(indexed_hover(&mut server, &mut id, &first), type_hover("Int"));
+    assert_eq!(indexed_hover(&mut server, &mut id, &second), type_hover("String"));
+    server.exit();
+}
+
+#[test]
+fn changed_workspace_folders() {
+    let first = folder("folder_changed_first", "let x = 1\nx\n");
+    let second = folder("folder_changed_second", "let x = \"\"\nx\n");
+
+    let mut server = support::Server::spawn();
+    support::initialize(server.stdin(), 1, &[uri(&first)]);
+    server.wait_for_response(1);
+    let mut id = 1;
+    assert_eq!(indexed_hover(&mut server, &mut id, &first), type_hover("Int"));
+
+    change_folders(&mut server, &[&second], &[&first]);
+    assert_eq!(indexed_hover(&mut server, &mut id, &second), type_hover("String"));
+    // The modules of the removed folder are no longer known
+    assert!(hover(&mut server, id + 1, &first).find("error").is_some());
+    server.exit();
+}
@@ -0,0 +1,63 @@
+extern crate gluon_language_server;
+extern crate languageserver_types;
+extern crate url;
+
+extern crate jsonrpc_core;
+extern crate serde_json;
+extern crate serde;
+
+mod support;
+
+use std::io::Write;
+
+use languageserver_types::{SymbolInformation, WorkspaceSymbolParams};
+
+fn workspace_symbols<W: ?Sized>(stdin: &mut W, id: u64, query: &str)
+    where W: Write,
+{
+    let symbols = support::method_call("workspace/symbol",
+                                       id,
+                                       WorkspaceSymbolParams { query: query.into() });
+
+    support::write_message(stdin, symbols).unwrap();
+}
+
+#[test]
+fn search_open_documents() {
+    let symbols: Vec<SymbolInformation> = support::send_rpc(|mut stdin| {
+        support::did_open(stdin, "test", "let test = 1\ntest");
+        support::did_open(stdin, "other", "let testing x = x\ntesting 1");
+
+        workspace_symbols(stdin, 2, "test");
+    });
+
+    // Only look at the opened documents as the standard library is indexed as well
+    let names: Vec<_> = symbols.iter()
+        .filter(|symbol| symbol.location.uri == "test" || symbol.location.uri == "other")
+        .map(|symbol| (&symbol.name[..], &symbol.location.uri[..]))
+        .collect();
+    assert_eq!(names, [("test", "test"), ("testing", "other")]);
+}
+
+#[test]
+fn open_and_imported_document_is_listed_once() {
+    let dir = support::temp_dir("open_and_imported_document_is_listed_once");
+    let other = support::write_file(&dir, "other.glu", "let xyz = 1\n{ xyz }\n");
+    let main = support::write_file(&dir, "main.glu", "let { xyz } = import \"other\"\nxyz\n");
+    let other_uri = url::Url::from_file_path(&other).unwrap().to_string();
+    let main_uri = url::Url::from_file_path(&main).unwrap().to_string();
+
+    let symbols: Vec<SymbolInformation> = support::send_rpc(|mut stdin| {
+        support::initialize(stdin, 1, &[url::Url::from_file_path(&dir).unwrap().to_string()]);
+        support::did_open(stdin, &other_uri, "let xyz = 1\n{ xyz }\n");
+        support::did_open(stdin, &main_uri, "let { xyz } = import \"other\"\nxyz\n");
+
+        workspace_symbols(stdin, 2, "xyz");
+    });
+
+    let locations: Vec<_> = symbols.iter()
+        .filter(|symbol| symbol.location.uri == other_uri)
+        .map(|symbol| symbol.location.range.start.line)
+        .collect();
+    assert_eq!(locations, [0, 1]);
+}